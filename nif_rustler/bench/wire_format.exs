@@ -0,0 +1,53 @@
+# Time of player_update and game_update with cards sent as Card records and as
+# binary.
+# Run with: mix run bench/wire_format.exs
+
+defmodule WireFormatBench do
+  require PlayerRust.CommandRemoveCards
+  require GameRust.ModelNone
+
+  @runs 10_000
+
+  def run() do
+    hand = Cards.make_deck() |> Enum.take(26)
+    msg = %PlayerRust.Msg{from: [0], command: PlayerRust.CommandRemoveCards.record(nr: 2)}
+
+    # the same hand in binary form, a query keeps the model
+    keep = %PlayerRust.Msg{from: [0], command: PlayerRust.CommandCountCards}
+    {binary_hand, _cmd} = Cardsnif.player_update(hand, keep, "WireFormat::Binary")
+
+    report("player_update", fn format ->
+      hand = if format == "WireFormat::Binary", do: binary_hand, else: hand
+      measure(&Cardsnif.player_update/3, hand, msg, format)
+    end)
+
+    # the deal, both hands are sent in the cmd
+    pids = %GameRust.Pids{supervisor: [0], players: [[1], [2]]}
+    game = GameRust.ModelNone.record(pids: pids, config: %GameRust.Config{seed: 42})
+
+    report("game_update", fn format ->
+      measure(&Cardsnif.game_update/3, game, "Msg::StartGame", format)
+    end)
+  end
+
+  defp report(name, measure) do
+    terms = measure.("WireFormat::Terms")
+    binary = measure.("WireFormat::Binary")
+    IO.puts("#{name} WireFormat::Terms:  #{Float.round(terms, 2)} microseconds")
+    IO.puts("#{name} WireFormat::Binary: #{Float.round(binary, 2)} microseconds")
+  end
+
+  defp measure(update, model, msg, format) do
+    # first run of a NIF function is slow
+    update.(model, msg, format)
+
+    {time, _} =
+      :timer.tc(fn ->
+        Enum.each(1..@runs, fn _ -> update.(model, msg, format) end)
+      end)
+
+    time / @runs
+  end
+end
+
+WireFormatBench.run()
@@ -2,10 +2,90 @@ defmodule Card do
   @moduledoc """
   Documentation for Card
   A card is a Erlang tuple (Elixir record) of form:
-  {Card, "Suit::Club", "Value::Ace"}
+  {Card, "Suit::Club", "Value::Ace", 0}
+  The last element is the number of the deck the card comes from, so copies of
+  a card in a shoe of many decks can be told apart.
+
+  Jokers have value "Value::Joker", red joker has suit "Suit::Heart" and black
+  joker "Suit::Spade".
   """
 
   require Record
-  @type t :: {__MODULE__, bitstring, bitstring}
-  Record.defrecord(:record, __MODULE__, suit: nil, value: nil)
+  @type t :: {__MODULE__, bitstring, bitstring, non_neg_integer}
+  Record.defrecord(:record, __MODULE__, suit: nil, value: nil, deck: 0)
+end
+
+defmodule CardRule do
+  @moduledoc """
+  Special rules for comparing cards, e.g.
+  {CardRule.BeatsAll, "Value::Joker"} or
+  {CardRule.Beats, "Value::Two", "Value::Ace"}
+  """
+
+  defmodule BeatsAll do
+    require Record
+    @type t :: {__MODULE__, bitstring}
+    Record.defrecord(:record, __MODULE__, value: nil)
+  end
+
+  defmodule Beats do
+    require Record
+    @type t :: {__MODULE__, bitstring, bitstring}
+    Record.defrecord(:record, __MODULE__, winner: nil, loser: nil)
+  end
+
+  @type t :: BeatsAll.t() | Beats.t()
+end
+
+defmodule CardTrump do
+  @moduledoc """
+  Trump suit of a game: "Trump::None", "Trump::Drawn" or
+  {CardTrump.Fixed, "Suit::Heart"}. Jokers are never trumps.
+  """
+
+  defmodule Fixed do
+    require Record
+    @type t :: {__MODULE__, bitstring}
+    Record.defrecord(:record, __MODULE__, suit: nil)
+  end
+
+  @type t :: bitstring | Fixed.t()
+end
+
+defmodule CardShuffling do
+  @moduledoc """
+  How the shoe is shuffled before the deal: "Shuffling::Uniform" (perfect
+  shuffle), {CardShuffling.Riffles, n} (n riffle shuffles, 7 mix a deck well)
+  or {CardShuffling.Overhand, n} (n overhand shuffles). Both end with a cut.
+  """
+
+  defmodule Riffles do
+    require Record
+    @type t :: {__MODULE__, non_neg_integer}
+    Record.defrecord(:record, __MODULE__, riffles: nil)
+  end
+
+  defmodule Overhand do
+    require Record
+    @type t :: {__MODULE__, non_neg_integer}
+    Record.defrecord(:record, __MODULE__, shuffles: nil)
+  end
+
+  @type t :: bitstring | Riffles.t() | Overhand.t()
+end
+
+defmodule CardFaceDown do
+  @moduledoc """
+  Cards each player puts face down in a war, under the face-up card:
+  {CardFaceDown.Cards, n} (n in every war) or "FaceDown::ByRank" (the tied
+  value counts as in cribbage: ace 1, pips, court cards 10).
+  """
+
+  defmodule Cards do
+    require Record
+    @type t :: {__MODULE__, non_neg_integer}
+    Record.defrecord(:record, __MODULE__, nr: 1)
+  end
+
+  @type t :: bitstring | Cards.t()
 end
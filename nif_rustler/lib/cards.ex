@@ -52,8 +52,8 @@ defmodule Cards do
   end
 
   @doc """
-  Create a deck of 52 cards in the form [{Card, "Suit::Club", "Value::Three"},
-  {Card, "Suit::Diamond", "Value::Ace"}...]
+  Create a deck of 52 cards in the form [{Card, "Suit::Club", "Value::Three", 0},
+  {Card, "Suit::Diamond", "Value::Ace", 0}...]
   """
   @spec make_deck() :: [Card.t()]
   def make_deck() do
@@ -92,7 +92,7 @@ defmodule Cards do
 
   ### Examples
 
-  iex(3)> Cards.value({Card, "Suit::Spade", "Value::Ace"})
+  iex(3)> Cards.value({Card, "Suit::Spade", "Value::Ace", 0})
   14
 
   """
@@ -100,7 +100,7 @@ defmodule Cards do
   @spec value(Card.t()) :: non_neg_integer
   def value(card) do
     case card do
-      {_, _, value} ->
+      {_, _, value, _} ->
         case value do
           "Value::Two" -> 2
           "Value::Three" -> 3
@@ -127,7 +127,7 @@ defmodule Cards do
 
   ### Examples
 
-  iex(7)> Cards.first_is_less({Card, "Suit::Spade", "Value::Two"}, {Card, "Suit::Heart", "Value::Queen"})
+  iex(7)> Cards.first_is_less({Card, "Suit::Spade", "Value::Two", 0}, {Card, "Suit::Heart", "Value::Queen", 0})
   false
 
   """
@@ -144,7 +144,7 @@ defmodule Cards do
 
   ### Examples
 
-  iex(8)> Cards.cards_are_equal({Card, "Suit::Spade", "Value::Ace"}, {Card, "Suit::Heart", "Value::Queen"})
+  iex(8)> Cards.cards_are_equal({Card, "Suit::Spade", "Value::Ace", 0}, {Card, "Suit::Heart", "Value::Queen", 0})
   false
 
   """
@@ -15,4 +15,39 @@ defmodule Cardsnif do
   def player_update(_, _), do: err()
   def game_update(_, _), do: err()
 
+  @doc """
+  As update functions above. The last argument chooses how lists of cards
+  (hands and piles) are returned: "WireFormat::Terms" - list of Card records or
+  "WireFormat::Binary" - binary with two bytes per card, index of the card
+  (0..53) and deck tag. Both forms are accepted as input.
+  """
+  def player_update(_, _, _), do: err()
+  def game_update(_, _, _), do: err()
+
+  @doc """
+  Parse cards in short notation, e.g. "AS KH 10D", "A♠ 10♥" or "🂡".
+  Returns {:ok, [Card.t()]} or {:error, reason}.
+  """
+  def parse_hand(_), do: err()
+
+  @doc """
+  audit(game_model, game_cmd, player_models)
+  Check that every card of the shoe is in exactly one place: a player's hand,
+  the game model or an AddCards command. Player models are PlayerRust.Model
+  (hand and winnings) or lists of cards. Call it when no responses from players
+  are on the way, e.g. when the game model is ModelBattle.
+  Returns {:ok, nr_of_cards} or {:error, reason}.
+  """
+  def audit(_, _, _), do: err()
+
+  @doc """
+  player_new(model) returns a handle of a player model kept in Rust, model is
+  PlayerRust.Model or list of cards. The handle can be passed to player_update
+  instead of the model, it is updated in place and returned as the new model,
+  so only cards moved by a command are converted. player_model(handle) returns
+  a copy of the model as PlayerRust.Model, audit accepts handles too.
+  """
+  def player_new(_), do: err()
+  def player_model(_), do: err()
+
 end
@@ -1,15 +1,25 @@
 defmodule PlayerRust do
   @moduledoc """
   Rust version of Player process
-  model: list of cards
+  model: handle of PlayerRust.Model kept in Rust (a model or a list of cards
+  as the hand are accepted too)
   """
   use Exceptional
   require GameRust.MsgResponseFromPlayer
 
+  # placement: "Placement::Bottom" or "Placement::Top" of the hand, or
+  # "Placement::Winnings" (a separate pile, shuffled into the hand when it runs out)
   defmodule CommandAddCards do
     require Record
-    @type t :: {__MODULE__, [Card.t()]}
-    Record.defrecord(:record, __MODULE__, cards: [])
+    @type t :: {__MODULE__, [Card.t()], bitstring}
+    Record.defrecord(:record, __MODULE__, cards: [], placement: "Placement::Bottom")
+  end
+
+  # the deal of a game: cards go into the hand, seed is the seed of the player
+  defmodule CommandDeal do
+    require Record
+    @type t :: {__MODULE__, [Card.t()], non_neg_integer}
+    Record.defrecord(:record, __MODULE__, cards: [], seed: 0)
   end
 
   defmodule CommandRemoveCards do
@@ -18,12 +28,68 @@ defmodule PlayerRust do
     Record.defrecord(:record, __MODULE__, nr: 0)
   end
 
+  # like RemoveCards, a player short of cards gives the rest of them
+  defmodule CommandRemoveUpTo do
+    require Record
+    @type t :: {__MODULE__, non_neg_integer}
+    Record.defrecord(:record, __MODULE__, nr: 0)
+  end
+
+  # Queries, the model is not changed. CountCards and HandSummary are sent as
+  # bare module names, e.g. command: PlayerRust.CommandCountCards
+  defmodule CommandCountCards do
+    @type t :: __MODULE__
+  end
+
+  defmodule CommandPeekTop do
+    require Record
+    @type t :: {__MODULE__, non_neg_integer}
+    Record.defrecord(:record, __MODULE__, nr: 0)
+  end
+
+  defmodule CommandHandSummary do
+    @type t :: __MODULE__
+  end
+
+  # commands run in order, if one of them fails the model is not changed
+  defmodule CommandBatch do
+    require Record
+    @type t :: {__MODULE__, [PlayerRust.command()]}
+    Record.defrecord(:record, __MODULE__, commands: [])
+  end
+
+  # sent as bare PlayerRust.CommandResign, the game which dealt the cards is told
+  # with PlayerRust.ResponseResigned, see resign/1
+  defmodule CommandResign do
+    @type t :: __MODULE__
+  end
+
+  # query sent as bare PlayerRust.CommandHandHash, the same hash for the same
+  # hand, winnings and seed
+  defmodule CommandHandHash do
+    @type t :: __MODULE__
+  end
+
+  @type command ::
+          CommandAddCards.t()
+          | CommandDeal.t()
+          | CommandRemoveCards.t()
+          | CommandRemoveUpTo.t()
+          | CommandCountCards.t()
+          | CommandPeekTop.t()
+          | CommandHandSummary.t()
+          | CommandBatch.t()
+          | CommandResign.t()
+          | CommandHandHash.t()
+
+  # id: round of the game, echoed in Cmd
   defmodule Msg do
     @type t :: %__MODULE__{
             from: [byte()],
-            command: CommandAddCards.t() | CommandRemoveCards.t()
+            command: PlayerRust.command(),
+            id: non_neg_integer
           }
-    defstruct from: [0], command: nil
+    defstruct from: [0], command: nil, id: 0
   end
 
   defmodule ResponseCardsAdded do
@@ -38,31 +104,146 @@ defmodule PlayerRust do
     Record.defrecord(:record, __MODULE__, cards: [])
   end
 
+  # the hand ran out and winnings were shuffled into it
+  defmodule ResponseCardsRemovedAfterReshuffle do
+    require Record
+    @type t :: {__MODULE__, [Card.t()]}
+    Record.defrecord(:record, __MODULE__, cards: [])
+  end
+
   defmodule ResponseUnableToRemoveCards do
     require Record
     @type t :: {__MODULE__, non_neg_integer}
     Record.defrecord(:record, __MODULE__, nr: 0)
   end
 
+  # fewer cards than asked by RemoveUpTo, the rest of them
+  defmodule ResponseCardsPartiallyRemoved do
+    require Record
+    @type t :: {__MODULE__, [Card.t()]}
+    Record.defrecord(:record, __MODULE__, cards: [])
+  end
+
+  defmodule ResponseCardsCounted do
+    require Record
+    @type t :: {__MODULE__, non_neg_integer}
+    Record.defrecord(:record, __MODULE__, nr: 0)
+  end
+
+  # top cards of the hand, the last one is the top
+  defmodule ResponseTopCards do
+    require Record
+    @type t :: {__MODULE__, [Card.t()]}
+    Record.defrecord(:record, __MODULE__, cards: [])
+  end
+
+  # values: [{"Value::Two", 3}, ...], suits: [{"Suit::Club", 5}, ...]
+  defmodule HandSummary do
+    @type t :: %__MODULE__{
+            values: [{bitstring, non_neg_integer}],
+            suits: [{bitstring, non_neg_integer}]
+          }
+    defstruct values: [], suits: []
+  end
+
+  defmodule ResponseHandSummary do
+    require Record
+    @type t :: {__MODULE__, HandSummary.t()}
+    Record.defrecord(:record, __MODULE__, summary: nil)
+  end
+
+  # Commands refused by the player: cards already held, RemoveCards with nr 0
+  # (sent as bare PlayerRust.ErrorNoCardsToRemove) or cards added or removed
+  # by another game than the one which dealt them
+  defmodule ErrorDuplicatedCards do
+    require Record
+    @type t :: {__MODULE__, [Card.t()]}
+    Record.defrecord(:record, __MODULE__, cards: [])
+  end
+
+  defmodule ErrorNoCardsToRemove do
+    @type t :: __MODULE__
+  end
+
+  defmodule ErrorWrongGame do
+    require Record
+    @type t :: {__MODULE__, [byte()]}
+    Record.defrecord(:record, __MODULE__, sender: [])
+  end
+
   defmodule ResponseError do
     require Record
-    @type t :: {__MODULE__, bitstring}
+    @type t ::
+            {__MODULE__, ErrorDuplicatedCards.t() | ErrorNoCardsToRemove.t() | ErrorWrongGame.t()}
     Record.defrecord(:record, __MODULE__, error: nil)
   end
 
+  # responses to the commands of a CommandBatch, a batch which can't be done is
+  # answered with only the response of the failing command and changes nothing
+  defmodule ResponseBatch do
+    require Record
+    @type t :: {__MODULE__, [PlayerRust.response()]}
+    Record.defrecord(:record, __MODULE__, responses: [])
+  end
+
+  defmodule ResponseResigned do
+    @type t :: __MODULE__
+  end
+
+  defmodule ResponseHandHash do
+    require Record
+    @type t :: {__MODULE__, non_neg_integer}
+    Record.defrecord(:record, __MODULE__, hash: 0)
+  end
+
+  @type response ::
+          ResponseCardsAdded.t()
+          | ResponseCardsRemoved.t()
+          | ResponseCardsRemovedAfterReshuffle.t()
+          | ResponseUnableToRemoveCards.t()
+          | ResponseCardsPartiallyRemoved.t()
+          | ResponseCardsCounted.t()
+          | ResponseTopCards.t()
+          | ResponseHandSummary.t()
+          | ResponseError.t()
+          | ResponseBatch.t()
+          | ResponseResigned.t()
+          | ResponseHandHash.t()
+
   defmodule Cmd do
     @type t :: %__MODULE__{
             game: [byte()],
-            response:
-              ResponseCardsAdded.t()
-              | ResponseCardsRemoved.t()
-              | ResponseUnableToRemoveCards.t()
-              | ResponseError.t()
+            response: PlayerRust.response(),
+            id: non_neg_integer
+          }
+    defstruct game: [0], response: nil, id: 0
+  end
+
+  # Hand: the last card is the top, winnings: see "Placement::Winnings",
+  # seed: seed of the next reshuffle of winnings (or of "Pickup::Random"), set
+  # by CommandDeal,
+  # pickup: how won cards are ordered in the hand - "Pickup::AsReceived",
+  # "Pickup::HighFirst", "Pickup::LowFirst", "Pickup::Random" or
+  # "Pickup::Interleave" (cards of both players alternate). High and low are by
+  # face value, Ace high, whatever the ranking of the game.
+  # game: pid (as list) of the game which dealt the cards, set by CommandDeal
+  # or the first CommandAddCards.
+  # Cards are lists or binaries, see Cardsnif.player_update/3
+  defmodule Model do
+    @type t :: %__MODULE__{
+            hand: [Card.t()] | binary,
+            winnings: [Card.t()] | binary,
+            seed: non_neg_integer,
+            pickup: bitstring,
+            game: [byte()] | nil
           }
-    defstruct game: [0], response: nil
+    defstruct hand: [], winnings: [], seed: 0, pickup: "Pickup::AsReceived", game: nil
   end
 
-  @type model :: [Card.t()]
+  # handle from Cardsnif.player_new/1
+  @type handle :: reference
+
+  @type model :: handle | Model.t() | [Card.t()] | binary
 
   @spec update(model, Msg.t()) :: {model, Cmd.t()}
   def update(model, msg) do
@@ -77,10 +258,10 @@ defmodule PlayerRust do
   @spec execute_cmd(Cmd.t()) :: nil
   def execute_cmd(cmd) do
     case cmd do
-      %PlayerRust.Cmd{game: game, response: response} ->
+      %PlayerRust.Cmd{game: game, response: response, id: id} ->
         game = Helper.list_to_pid(game)
         player = Helper.pid_to_list(self())
-        msg = GameRust.MsgResponseFromPlayer.record(player: player, response: response)
+        msg = GameRust.MsgResponseFromPlayer.record(player: player, response: response, id: id)
         send(game, msg)
 
       {:error, {erlang_error, _msg}} ->
@@ -88,8 +269,15 @@ defmodule PlayerRust do
     end
   end
 
+  # the player leaves its game
+  @spec resign(pid) :: nil
+  def resign(player) do
+    send(player, %Msg{from: Helper.pid_to_list(self()), command: CommandResign})
+    nil
+  end
+
   @spec process(model) :: nil
-  def process(model \\ []) do
+  def process(model \\ Cardsnif.player_new(%Model{})) do
     receive do
       msg ->
         {model, cmd} = update(model, msg)
@@ -1,8 +1,14 @@
 #![allow(dead_code)]
 
 extern crate rand;
+use self::rand::Rng;
+use rustler::types::binary::{Binary, OwnedBinary};
 use rustler::types::tuple::{get_tuple, make_tuple};
 use rustler::{Decoder, Encoder, Env, Error, NifResult, Term};
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Suit {
@@ -58,6 +64,7 @@ pub enum Value {
     Queen,
     King,
     Ace,
+    Joker, // see RED_JOKER and BLACK_JOKER
 }
 
 impl<'a> Decoder<'a> for Value {
@@ -77,6 +84,7 @@ impl<'a> Decoder<'a> for Value {
             "Value::Queen" => Ok(Value::Queen),
             "Value::King" => Ok(Value::King),
             "Value::Ace" => Ok(Value::Ace),
+            "Value::Joker" => Ok(Value::Joker),
             _ => Err(Error::BadArg),
         }
     }
@@ -98,6 +106,7 @@ impl Encoder for Value {
             Value::Queen => "Value::Queen",
             Value::King => "Value::King",
             Value::Ace => "Value::Ace",
+            Value::Joker => "Value::Joker",
         };
         value.encode(env)
     }
@@ -123,12 +132,81 @@ pub fn get_values() -> Values {
     ]
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum DeckSpec {
+    #[default]
+    Standard, // 52 cards
+    Jokers, // 54 cards, standard deck with red and black joker
+    Short,  // 36 cards, Six to Ace
+    Piquet, // 32 cards, Seven to Ace
+}
+
+impl<'a> Decoder<'a> for DeckSpec {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        let spec: &str = Decoder::decode(term)?;
+        match spec {
+            "DeckSpec::Piquet" => Ok(DeckSpec::Piquet),
+            "DeckSpec::Short" => Ok(DeckSpec::Short),
+            "DeckSpec::Standard" => Ok(DeckSpec::Standard),
+            "DeckSpec::Jokers" => Ok(DeckSpec::Jokers),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for DeckSpec {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        let spec: &str = match self {
+            DeckSpec::Piquet => "DeckSpec::Piquet",
+            DeckSpec::Short => "DeckSpec::Short",
+            DeckSpec::Standard => "DeckSpec::Standard",
+            DeckSpec::Jokers => "DeckSpec::Jokers",
+        };
+        spec.encode(env)
+    }
+}
+
+// Ranking policy of a game, see RankingPolicy
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Ranking {
+    #[default]
+    AceHigh,
+    AceLow,
+    SuitTiebreak,
+    Peace,
+}
+
+impl<'a> Decoder<'a> for Ranking {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        let ranking: &str = Decoder::decode(term)?;
+        match ranking {
+            "Ranking::AceHigh" => Ok(Ranking::AceHigh),
+            "Ranking::AceLow" => Ok(Ranking::AceLow),
+            "Ranking::SuitTiebreak" => Ok(Ranking::SuitTiebreak),
+            "Ranking::Peace" => Ok(Ranking::Peace),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for Ranking {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        let ranking: &str = match self {
+            Ranking::AceHigh => "Ranking::AceHigh",
+            Ranking::AceLow => "Ranking::AceLow",
+            Ranking::SuitTiebreak => "Ranking::SuitTiebreak",
+            Ranking::Peace => "Ranking::Peace",
+        };
+        ranking.encode(env)
+    }
+}
+
 // #[derive(NifRecord)]
 // #[rustler(encode, decode)]
 // #[must_use]
 // #[tag = "record"]
 #[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Card(pub Suit, pub Value);
+pub struct Card(pub Suit, pub Value, pub u8); // suit, value, deck in a shoe
 
 impl Card {
     pub fn value(&self) -> u32 {
@@ -146,18 +224,31 @@ impl Card {
             Value::Queen => 12,
             Value::King => 13,
             Value::Ace => 14,
+            Value::Joker => 15,
         }
     }
+
+    pub fn is_joker(&self) -> bool {
+        self.1 == Value::Joker
+    }
+
+    // jokers are never trumps
+    pub fn is_trump(&self, trump: Suit) -> bool {
+        self.0 == trump && !self.is_joker()
+    }
 }
 
+pub const RED_JOKER: Card = Card(Suit::Heart, Value::Joker, 0);
+pub const BLACK_JOKER: Card = Card(Suit::Spade, Value::Joker, 0);
+
 impl<'a> Decoder<'a> for Card {
     fn decode(term: Term<'a>) -> NifResult<Self> {
         let terms = get_tuple(term)?;
         match terms.as_slice() {
-            [card, suit, value] => {
+            [card, suit, value, deck] => {
                 let env = term.get_env();
                 if *card == ::atoms::card().encode(env) {
-                    Ok(Card(suit.decode()?, value.decode()?))
+                    Ok(Card(suit.decode()?, value.decode()?, deck.decode()?))
                 } else {
                     Err(Error::BadArg)
                 }
@@ -169,68 +260,619 @@ impl<'a> Decoder<'a> for Card {
 
 impl Encoder for Card {
     fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
-        let (suit, value) = match self {
-            Card(suit, value) => (suit, value),
+        let (suit, value, deck) = match self {
+            Card(suit, value, deck) => (suit, value, deck),
         };
         let card = vec![
             ::atoms::card().encode(env),
             suit.encode(env),
             value.encode(env),
+            deck.encode(env),
         ];
         make_tuple(env, &card)
     }
 }
 
-pub fn first_is_less(first: &Card, second: &Card) -> bool {
-    first.value() < second.value()
+// Special rules change the order of values, e.g. BeatsAll(Value::Joker) or
+// Beats(Value::Two, Value::Ace). The first rule that applies decides.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Rule {
+    BeatsAll(Value),     // value beats every other value
+    Beats(Value, Value), // first value beats second one
+}
+
+impl<'a> Decoder<'a> for Rule {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        let terms = get_tuple(term)?;
+        let env = term.get_env();
+        match terms.as_slice() {
+            [rule, value] => {
+                if *rule == ::atoms::card_rule_beats_all().encode(env) {
+                    Ok(Rule::BeatsAll(value.decode()?))
+                } else {
+                    Err(Error::BadArg)
+                }
+            }
+            [rule, winner, loser] => {
+                if *rule == ::atoms::card_rule_beats().encode(env) {
+                    Ok(Rule::Beats(winner.decode()?, loser.decode()?))
+                } else {
+                    Err(Error::BadArg)
+                }
+            }
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for Rule {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        match self {
+            Rule::BeatsAll(value) => {
+                let rule = vec![
+                    ::atoms::card_rule_beats_all().encode(env),
+                    value.encode(env),
+                ];
+                make_tuple(env, &rule)
+            }
+            Rule::Beats(winner, loser) => {
+                let rule = vec![
+                    ::atoms::card_rule_beats().encode(env),
+                    winner.encode(env),
+                    loser.encode(env),
+                ];
+                make_tuple(env, &rule)
+            }
+        }
+    }
+}
+
+// Trump suit of a game. Drawn trump is the suit of the bottom card of the
+// shuffled shoe, after the deal it is recorded as Fixed.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Trump {
+    #[default]
+    None,
+    Fixed(Suit),
+    Drawn,
+}
+
+impl<'a> Decoder<'a> for Trump {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        if let Ok(trump) = term.decode::<&str>() {
+            return match trump {
+                "Trump::None" => Ok(Trump::None),
+                "Trump::Drawn" => Ok(Trump::Drawn),
+                _ => Err(Error::BadArg),
+            };
+        }
+        let terms = get_tuple(term)?;
+        let env = term.get_env();
+        match terms.as_slice() {
+            [fixed, suit] => {
+                if *fixed == ::atoms::card_trump_fixed().encode(env) {
+                    Ok(Trump::Fixed(suit.decode()?))
+                } else {
+                    Err(Error::BadArg)
+                }
+            }
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for Trump {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        match self {
+            Trump::None => "Trump::None".encode(env),
+            Trump::Drawn => "Trump::Drawn".encode(env),
+            Trump::Fixed(suit) => {
+                let fixed = vec![::atoms::card_trump_fixed().encode(env), suit.encode(env)];
+                make_tuple(env, &fixed)
+            }
+        }
+    }
+}
+
+// Cards each player puts face down in a war, under the face-up card
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FaceDown {
+    Cards(u8), // the same nr in every war
+    ByRank,    // the tied value counts as in cribbage: ace 1, pips, court cards 10
+}
+
+impl Default for FaceDown {
+    fn default() -> Self {
+        FaceDown::Cards(1)
+    }
+}
+
+impl<'a> Decoder<'a> for FaceDown {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        if let Ok(face_down) = term.decode::<&str>() {
+            return match face_down {
+                "FaceDown::ByRank" => Ok(FaceDown::ByRank),
+                _ => Err(Error::BadArg),
+            };
+        }
+        let terms = get_tuple(term)?;
+        let env = term.get_env();
+        match terms.as_slice() {
+            [face_down, nr] if *face_down == ::atoms::card_face_down_cards().encode(env) => {
+                Ok(FaceDown::Cards(nr.decode()?))
+            }
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for FaceDown {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        match self {
+            FaceDown::Cards(nr) => {
+                let cards = vec![::atoms::card_face_down_cards().encode(env), nr.encode(env)];
+                make_tuple(env, &cards)
+            }
+            FaceDown::ByRank => "FaceDown::ByRank".encode(env),
+        }
+    }
+}
+
+// How the shoe is shuffled before the deal, see human_shuffle
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Shuffling {
+    #[default]
+    Uniform, // Fisher-Yates
+    Riffles(u8),  // nr of Gilbert-Shannon-Reeds riffles, then a cut
+    Overhand(u8), // nr of overhand shuffles, then a cut
+}
+
+impl<'a> Decoder<'a> for Shuffling {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        if let Ok(shuffling) = term.decode::<&str>() {
+            return match shuffling {
+                "Shuffling::Uniform" => Ok(Shuffling::Uniform),
+                _ => Err(Error::BadArg),
+            };
+        }
+        let terms = get_tuple(term)?;
+        let env = term.get_env();
+        match terms.as_slice() {
+            [shuffling, nr] => {
+                if *shuffling == ::atoms::card_shuffling_riffles().encode(env) {
+                    Ok(Shuffling::Riffles(nr.decode()?))
+                } else if *shuffling == ::atoms::card_shuffling_overhand().encode(env) {
+                    Ok(Shuffling::Overhand(nr.decode()?))
+                } else {
+                    Err(Error::BadArg)
+                }
+            }
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for Shuffling {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        match self {
+            Shuffling::Uniform => "Shuffling::Uniform".encode(env),
+            Shuffling::Riffles(nr) => {
+                let riffles = vec![
+                    ::atoms::card_shuffling_riffles().encode(env),
+                    nr.encode(env),
+                ];
+                make_tuple(env, &riffles)
+            }
+            Shuffling::Overhand(nr) => {
+                let overhand = vec![
+                    ::atoms::card_shuffling_overhand().encode(env),
+                    nr.encode(env),
+                ];
+                make_tuple(env, &overhand)
+            }
+        }
+    }
+}
+
+// Format of encoded lists of cards (hands and piles), chosen per NIF call.
+// Binary form has two bytes per card: index of the card and deck tag.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum WireFormat {
+    #[default]
+    Terms,
+    Binary,
+}
+
+impl<'a> Decoder<'a> for WireFormat {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        let format: &str = Decoder::decode(term)?;
+        match format {
+            "WireFormat::Terms" => Ok(WireFormat::Terms),
+            "WireFormat::Binary" => Ok(WireFormat::Binary),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for WireFormat {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        let format: &str = match self {
+            WireFormat::Terms => "WireFormat::Terms",
+            WireFormat::Binary => "WireFormat::Binary",
+        };
+        format.encode(env)
+    }
+}
+
+// list of Card records or binary, whatever the wire format
+pub fn decode_cards(term: Term) -> NifResult<Vec<Card>> {
+    if term.is_binary() {
+        let binary = Binary::from_term(term)?;
+        cards_from_binary(binary.as_slice()).map_err(|_| Error::BadArg)
+    } else {
+        term.decode()
+    }
+}
+
+pub fn encode_cards<'b>(cards: &[Card], env: Env<'b>) -> Term<'b> {
+    match wire_format() {
+        WireFormat::Terms => cards.encode(env),
+        WireFormat::Binary => {
+            let bytes = cards_to_binary(cards);
+            let mut binary = OwnedBinary::new(bytes.len()).unwrap();
+            binary.as_mut_slice().copy_from_slice(&bytes);
+            binary.release(env).encode(env)
+        }
+    }
+}
+
+/*
+Ranking policy orders two cards in a fight: Greater means the first card wins,
+Equal means war.
+*/
+pub trait RankingPolicy {
+    fn compare(&self, first: &Card, second: &Card) -> Ordering;
+}
+
+// Ace is the highest card
+pub struct AceHigh;
+
+impl RankingPolicy for AceHigh {
+    fn compare(&self, first: &Card, second: &Card) -> Ordering {
+        first.value().cmp(&second.value())
+    }
+}
+
+// Ace is the lowest card, below Two
+pub struct AceLow;
+
+fn ace_low_value(card: &Card) -> u32 {
+    match card.1 {
+        Value::Ace => 1,
+        _ => card.value(),
+    }
+}
+
+impl RankingPolicy for AceLow {
+    fn compare(&self, first: &Card, second: &Card) -> Ordering {
+        ace_low_value(first).cmp(&ace_low_value(second))
+    }
+}
+
+// Ace high, cards of equal value are ordered by suit: Club < Diamond < Heart <
+// Spade. Only copies of the same card from different decks make a war.
+pub struct SuitTiebreak;
+
+fn suit_order(suit: Suit) -> u32 {
+    match suit {
+        Suit::Club => 1,
+        Suit::Diamond => 2,
+        Suit::Heart => 3,
+        Suit::Spade => 4,
+    }
+}
+
+impl RankingPolicy for SuitTiebreak {
+    fn compare(&self, first: &Card, second: &Card) -> Ordering {
+        AceHigh
+            .compare(first, second)
+            .then_with(|| suit_order(first.0).cmp(&suit_order(second.0)))
+    }
+}
+
+// Peace: the lowest card wins
+pub struct Peace;
+
+impl RankingPolicy for Peace {
+    fn compare(&self, first: &Card, second: &Card) -> Ordering {
+        AceHigh.compare(second, first)
+    }
+}
+
+impl Ranking {
+    pub fn policy(self) -> &'static dyn RankingPolicy {
+        match self {
+            Ranking::AceHigh => &AceHigh,
+            Ranking::AceLow => &AceLow,
+            Ranking::SuitTiebreak => &SuitTiebreak,
+            Ranking::Peace => &Peace,
+        }
+    }
+}
+
+// A trump beats any card which is not a trump, the policy orders cards of the
+// same trump status.
+pub struct WithTrump<'a> {
+    pub suit: Suit,
+    pub policy: &'a dyn RankingPolicy,
+}
+
+impl<'a> RankingPolicy for WithTrump<'a> {
+    fn compare(&self, first: &Card, second: &Card) -> Ordering {
+        let first_trump = first.is_trump(self.suit);
+        let second_trump = second.is_trump(self.suit);
+        first_trump
+            .cmp(&second_trump)
+            .then_with(|| self.policy.compare(first, second))
+    }
+}
+
+impl Trump {
+    // Drawn trump becomes Fixed with the suit of the bottom card of the shoe
+    // (jokers are skipped), other variants are left as they are.
+    pub fn draw(self, shoe: &[Card]) -> Trump {
+        match self {
+            Trump::Drawn => shoe
+                .iter()
+                .rev()
+                .find(|card| !card.is_joker())
+                .map_or(Trump::None, |card| Trump::Fixed(card.0)),
+            trump => trump,
+        }
+    }
 }
 
 #[cfg(test)]
-mod first_is_less {
+mod ranking_policy {
     use super::*;
 
     #[test]
-    fn two_less_than_ace() {
-        let first = Card(Suit::Club, Value::Two);
-        let second = Card(Suit::Heart, Value::Ace);
-        assert_eq!(first_is_less(&first, &second), true);
+    fn ace_low_loses_to_two() {
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let two = Card(Suit::Club, Value::Two, 0);
+        assert_eq!(AceLow.compare(&ace, &two), Ordering::Less);
+        assert_eq!(AceHigh.compare(&ace, &two), Ordering::Greater);
     }
 
     #[test]
-    fn jack_not_less_than_nine() {
-        let first = Card(Suit::Diamond, Value::Jack);
-        let second = Card(Suit::Spade, Value::Nine);
-        assert_eq!(first_is_less(&first, &second), false);
+    fn suit_tiebreak_removes_wars() {
+        let club = Card(Suit::Club, Value::Ten, 0);
+        let spade = Card(Suit::Spade, Value::Ten, 0);
+        let jack = Card(Suit::Club, Value::Jack, 0);
+        assert_eq!(SuitTiebreak.compare(&club, &spade), Ordering::Less);
+        assert_eq!(SuitTiebreak.compare(&spade, &club), Ordering::Greater);
+        assert_eq!(SuitTiebreak.compare(&spade, &jack), Ordering::Less);
+        let copy = Card(Suit::Club, Value::Ten, 1);
+        assert_eq!(SuitTiebreak.compare(&club, &copy), Ordering::Equal);
+    }
+
+    #[test]
+    fn peace_lowest_wins() {
+        let two = Card(Suit::Club, Value::Two, 0);
+        let king = Card(Suit::Heart, Value::King, 0);
+        assert_eq!(Peace.compare(&two, &king), Ordering::Greater);
+        assert_eq!(
+            Peace.compare(&two, &Card(Suit::Spade, Value::Two, 0)),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn trump_beats_higher_card() {
+        let policy = WithTrump {
+            suit: Suit::Heart,
+            policy: &AceHigh,
+        };
+        let two = Card(Suit::Heart, Value::Two, 0);
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let king = Card(Suit::Club, Value::King, 0);
+        assert_eq!(policy.compare(&two, &ace), Ordering::Greater);
+        assert_eq!(policy.compare(&king, &ace), Ordering::Less);
+        assert_eq!(
+            policy.compare(&two, &Card(Suit::Heart, Value::Three, 0)),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn joker_is_never_trump() {
+        let policy = WithTrump {
+            suit: Suit::Heart,
+            policy: &AceHigh,
+        };
+        let two = Card(Suit::Heart, Value::Two, 0);
+        assert_eq!(policy.compare(&RED_JOKER, &two), Ordering::Less);
+    }
+
+    #[test]
+    fn draw_trump_from_bottom_card() {
+        let shoe = [
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Diamond, Value::Ace, 0),
+            RED_JOKER,
+        ];
+        assert_eq!(Trump::Drawn.draw(&shoe), Trump::Fixed(Suit::Diamond));
+        assert_eq!(Trump::None.draw(&shoe), Trump::None);
+        assert_eq!(
+            Trump::Fixed(Suit::Spade).draw(&shoe),
+            Trump::Fixed(Suit::Spade)
+        );
+        assert_eq!(Trump::Drawn.draw(&[]), Trump::None);
+    }
+
+    #[test]
+    fn policy_of_ranking() {
+        let two = Card(Suit::Club, Value::Two, 0);
+        let ace = Card(Suit::Heart, Value::Ace, 0);
+        assert_eq!(Ranking::default(), Ranking::AceHigh);
+        assert_eq!(
+            Ranking::AceLow.policy().compare(&two, &ace),
+            Ordering::Greater
+        );
+        assert_eq!(
+            Ranking::Peace.policy().compare(&two, &ace),
+            Ordering::Greater
+        );
+        assert_eq!(
+            Ranking::SuitTiebreak.policy().compare(&two, &ace),
+            Ordering::Less
+        );
+    }
+}
+
+fn rule_order(rule: &Rule, first: &Card, second: &Card) -> Option<Ordering> {
+    match *rule {
+        Rule::BeatsAll(value) => match (first.1 == value, second.1 == value) {
+            (true, false) => Some(Ordering::Greater),
+            (false, true) => Some(Ordering::Less),
+            (_, _) => None,
+        },
+        Rule::Beats(winner, loser) => {
+            if (first.1, second.1) == (winner, loser) {
+                Some(Ordering::Greater)
+            } else if (first.1, second.1) == (loser, winner) {
+                Some(Ordering::Less)
+            } else {
+                None
+            }
+        }
     }
 }
 
-pub fn cards_are_equal(first: &Card, second: &Card) -> bool {
-    first.value() == second.value()
+// Order of cards in a fight: special rules first, then the ranking policy.
+pub fn compare(
+    policy: &dyn RankingPolicy,
+    rules: &[Rule],
+    first: &Card,
+    second: &Card,
+) -> Ordering {
+    rules
+        .iter()
+        .find_map(|rule| rule_order(rule, first, second))
+        .unwrap_or_else(|| policy.compare(first, second))
 }
 
 #[cfg(test)]
-mod cards_are_equal {
+mod compare {
     use super::*;
 
     #[test]
-    fn spade_ace_eq_diamond_ace() {
-        let spade_ace = Card(Suit::Spade, Value::Ace);
-        let diamond_ace = Card(Suit::Diamond, Value::Ace);
-        assert_eq!(cards_are_equal(&spade_ace, &diamond_ace), true);
+    fn joker_beats_ace() {
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        assert_eq!(compare(&AceHigh, &[], &RED_JOKER, &ace), Ordering::Greater);
+    }
+
+    #[test]
+    fn jokers_are_equal() {
+        assert_eq!(
+            compare(&AceHigh, &[], &RED_JOKER, &BLACK_JOKER),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn two_beats_ace() {
+        let rules = [Rule::Beats(Value::Two, Value::Ace)];
+        let two = Card(Suit::Club, Value::Two, 0);
+        let ace = Card(Suit::Heart, Value::Ace, 0);
+        let king = Card(Suit::Heart, Value::King, 0);
+        assert_eq!(compare(&AceHigh, &rules, &two, &ace), Ordering::Greater);
+        assert_eq!(compare(&AceHigh, &rules, &ace, &two), Ordering::Less);
+        assert_eq!(compare(&AceHigh, &rules, &two, &king), Ordering::Less);
+    }
+
+    #[test]
+    fn first_rule_that_applies_decides() {
+        let rules = [
+            Rule::Beats(Value::Two, Value::Joker),
+            Rule::BeatsAll(Value::Joker),
+        ];
+        let two = Card(Suit::Club, Value::Two, 0);
+        let ace = Card(Suit::Heart, Value::Ace, 0);
+        assert_eq!(
+            compare(&AceHigh, &rules, &two, &BLACK_JOKER),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare(&AceHigh, &rules, &ace, &BLACK_JOKER),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn beats_all_keeps_ties() {
+        let rules = [Rule::BeatsAll(Value::Two)];
+        let two = Card(Suit::Club, Value::Two, 0);
+        assert_eq!(
+            compare(&AceHigh, &rules, &two, &RED_JOKER),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare(&AceHigh, &rules, &two, &Card(Suit::Heart, Value::Two, 0)),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn rules_override_policy() {
+        let rules = [Rule::BeatsAll(Value::Joker)];
+        let two = Card(Suit::Club, Value::Two, 0);
+        assert_eq!(compare(&Peace, &[], &two, &RED_JOKER), Ordering::Greater);
+        assert_eq!(compare(&Peace, &rules, &two, &RED_JOKER), Ordering::Less);
     }
 }
 
-fn make_deck() -> [Card; 52] {
+impl DeckSpec {
+    fn lowest_value(self) -> u32 {
+        match self {
+            DeckSpec::Piquet => 7,
+            DeckSpec::Short => 6,
+            DeckSpec::Standard | DeckSpec::Jokers => 2,
+        }
+    }
+
+    fn jokers(self) -> Vec<Card> {
+        match self {
+            DeckSpec::Jokers => vec![RED_JOKER, BLACK_JOKER],
+            _ => vec![],
+        }
+    }
+
+    pub fn size(self) -> usize {
+        let values = get_values()
+            .iter()
+            .filter(|value| Card(Suit::Club, **value, 0).value() >= self.lowest_value())
+            .count();
+        4 * values + self.jokers().len()
+    }
+}
+
+// cards of the deck are tagged with its number in a shoe
+fn make_deck(spec: DeckSpec, nr: u8) -> Vec<Card> {
     let suits = get_suits();
     let values = get_values();
-    let mut deck: [Card; 52] = [Card(Suit::Spade, Value::Ace); 52];
-    let mut i = 0;
+    let mut deck = Vec::with_capacity(spec.size());
     for suit in suits.iter() {
         for value in values.iter() {
-            deck[i] = Card(*suit, *value);
-            i += 1;
+            let card = Card(*suit, *value, nr);
+            if card.value() >= spec.lowest_value() {
+                deck.push(card);
+            }
         }
     }
+    for Card(suit, value, _) in spec.jokers() {
+        deck.push(Card(suit, value, nr));
+    }
     deck
 }
 
@@ -240,40 +882,450 @@ mod make_deck {
 
     #[test]
     fn len_of_deck_is_52() {
-        let deck = make_deck();
+        let deck = make_deck(DeckSpec::Standard, 0);
         assert_eq!(deck.len(), 52);
     }
 
     #[test]
     fn check_first_4_cards() {
-        let deck = make_deck();
+        let deck = make_deck(DeckSpec::Standard, 0);
         let four_cards = [
-            Card(Suit::Club, Value::Two),
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Club, Value::Four),
-            Card(Suit::Club, Value::Five),
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Club, Value::Three, 0),
+            Card(Suit::Club, Value::Four, 0),
+            Card(Suit::Club, Value::Five, 0),
         ];
         assert_eq!(deck[0..4], four_cards[..]);
     }
+
+    #[test]
+    fn len_matches_spec_size() {
+        for spec in [
+            DeckSpec::Piquet,
+            DeckSpec::Short,
+            DeckSpec::Standard,
+            DeckSpec::Jokers,
+        ]
+        .iter()
+        {
+            assert_eq!(make_deck(*spec, 0).len(), spec.size());
+        }
+        assert_eq!(DeckSpec::Piquet.size(), 32);
+        assert_eq!(DeckSpec::Short.size(), 36);
+        assert_eq!(DeckSpec::Jokers.size(), 54);
+    }
+
+    #[test]
+    fn piquet_starts_with_seven() {
+        let deck = make_deck(DeckSpec::Piquet, 0);
+        assert_eq!(deck[0], Card(Suit::Club, Value::Seven, 0));
+        assert!(deck.iter().all(|card| card.value() >= 7));
+    }
+
+    #[test]
+    fn jokers_at_the_end() {
+        let deck = make_deck(DeckSpec::Jokers, 0);
+        assert_eq!(deck[52..], [RED_JOKER, BLACK_JOKER]);
+    }
+
+    #[test]
+    fn cards_tagged_with_deck_nr() {
+        let deck = make_deck(DeckSpec::Jokers, 3);
+        assert!(deck.iter().all(|card| card.2 == 3));
+    }
+}
+
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
 }
 
-pub fn shuffled_deck() -> [Card; 52] {
-    use cards::rand::prelude::*;
-    let mut deck = make_deck();
-    let len = deck.len();
-    // println!("len: {}", len);
-    for i in 0..(len - 2) {
-        let ind = rand::thread_rng().gen_range(i + 1, len);
-        // println!("i: {}, ind: {}",i , ind);
-        deck.swap(i, ind);
+// Fisher-Yates shuffle: every permutation is equally likely and any card can
+// end up in any position, including the one it started in.
+pub fn shuffle<T, R: Rng + ?Sized>(cards: &mut [T], rng: &mut R) {
+    for i in (1..cards.len()).rev() {
+        let ind = rng.gen_range(0, i + 1);
+        cards.swap(i, ind);
+    }
+}
+
+// Reproducible shuffle, returns the seed for the next one
+pub fn shuffle_seeded<T>(cards: &mut [T], seed: u64) -> u64 {
+    use cards::rand::rngs::StdRng;
+    use cards::rand::SeedableRng;
+    let mut rng = StdRng::seed_from_u64(seed);
+    shuffle(cards, &mut rng);
+    rng.gen()
+}
+
+// Seeds of nr players drawn from the seed of a game, a replayed game gives
+// the same ones
+pub fn player_seeds(seed: u64, nr: usize) -> Vec<u64> {
+    use cards::rand::rngs::StdRng;
+    use cards::rand::SeedableRng;
+    let mut rng = StdRng::seed_from_u64(!seed); // not the stream of the shoe
+    (0..nr).map(|_| rng.gen()).collect()
+}
+
+#[cfg(test)]
+mod player_seeds {
+    use super::*;
+
+    #[test]
+    fn own_seed_for_every_player() {
+        let seeds = player_seeds(42, 3);
+        assert_eq!(seeds, player_seeds(42, 3));
+        assert!(seeds[0] != seeds[1] && seeds[1] != seeds[2]);
+        assert_ne!(seeds, player_seeds(43, 3));
     }
-    deck
 }
 
 #[cfg(test)]
-mod shuffled_deck {
+mod shuffle {
+    use super::*;
+    use cards::rand::rngs::StdRng;
+    use cards::rand::SeedableRng;
+
+    const SHUFFLES: usize = 24_000;
+
+    // Sattolo's algorithm used by shuffled_deck before - it never leaves a
+    // card in place and serves to check that the tests below can fail.
+    fn sattolo<T, R: Rng>(cards: &mut [T], rng: &mut R) {
+        let len = cards.len();
+        for i in 0..(len - 1) {
+            let ind = rng.gen_range(i + 1, len);
+            cards.swap(i, ind);
+        }
+    }
+
+    fn chi_square(observed: &[usize], expected: f64) -> f64 {
+        observed
+            .iter()
+            .map(|&o| (o as f64 - expected).powi(2) / expected)
+            .sum()
+    }
+
+    // counts[card][position]
+    fn position_counts(shuffle: fn(&mut [usize], &mut StdRng), shuffles: usize) -> Vec<Vec<usize>> {
+        let mut rng = StdRng::seed_from_u64(2019);
+        let mut counts = vec![vec![0; 52]; 52];
+        for _ in 0..shuffles {
+            let mut deck: Vec<usize> = (0..52).collect();
+            shuffle(&mut deck, &mut rng);
+            for (position, card) in deck.iter().enumerate() {
+                counts[*card][position] += 1;
+            }
+        }
+        counts
+    }
+
+    fn position_chi_square(counts: &[Vec<usize>], shuffles: usize) -> f64 {
+        let expected = shuffles as f64 / 52.0;
+        counts.iter().map(|row| chi_square(row, expected)).sum()
+    }
+
+    // 0.999 quantile of chi-square with 51 * 51 = 2601 degrees of freedom
+    // (normal approximation).
+    const POSITION_CRITICAL: f64 = 2824.0;
+
+    #[test]
+    fn permutations_of_four_cards_are_uniform() {
+        let mut rng = StdRng::seed_from_u64(52);
+        let cards = [
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Diamond, Value::Three, 0),
+            Card(Suit::Heart, Value::Four, 0),
+            Card(Suit::Spade, Value::Five, 0),
+        ];
+        let mut permutations: Vec<[Card; 4]> = Vec::new();
+        let mut counts: Vec<usize> = Vec::new();
+        for _ in 0..SHUFFLES {
+            let mut hand = cards;
+            shuffle(&mut hand, &mut rng);
+            match permutations.iter().position(|p| *p == hand) {
+                Some(i) => counts[i] += 1,
+                None => {
+                    permutations.push(hand);
+                    counts.push(1);
+                }
+            }
+        }
+        assert_eq!(permutations.len(), 24);
+        // 23 degrees of freedom, critical value for p = 0.001 is 49.73
+        let statistic = chi_square(&counts, SHUFFLES as f64 / 24.0);
+        assert!(statistic < 49.73, "chi-square: {}", statistic);
+    }
+
+    #[test]
+    fn every_card_visits_every_position() {
+        let shuffles = 5_200;
+        let counts = position_counts(shuffle, shuffles);
+        assert!(counts.iter().all(|row| row.iter().all(|&c| c > 0)));
+        let statistic = position_chi_square(&counts, shuffles);
+        assert!(statistic < POSITION_CRITICAL, "chi-square: {}", statistic);
+    }
+
+    #[test]
+    fn cards_stay_in_place_one_time_in_52() {
+        let shuffles = 5_200;
+        let counts = position_counts(shuffle, shuffles);
+        let in_place: Vec<usize> = (0..52).map(|card| counts[card][card]).collect();
+        let total: usize = in_place.iter().sum();
+        // on average one fixed point per shuffle
+        let mean = total as f64 / shuffles as f64;
+        assert!(
+            mean > 0.9 && mean < 1.1,
+            "fixed points per shuffle: {}",
+            mean
+        );
+    }
+
+    #[test]
+    fn sattolo_fails_position_test() {
+        let shuffles = 5_200;
+        let counts = position_counts(sattolo, shuffles);
+        assert!((0..52).all(|card| counts[card][card] == 0));
+        assert!(position_chi_square(&counts, shuffles) > POSITION_CRITICAL);
+    }
+
+    #[test]
+    fn empty_and_single_card() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut empty: [Card; 0] = [];
+        shuffle(&mut empty, &mut rng);
+        let mut single = [Card(Suit::Heart, Value::Ace, 0)];
+        shuffle(&mut single, &mut rng);
+        assert_eq!(single, [Card(Suit::Heart, Value::Ace, 0)]);
+    }
+}
+
+/*
+Shuffles made by hand. Gilbert-Shannon-Reeds model of a riffle: the deck is cut
+binomially and cards drop from each half with probability proportional to its
+size. Overhand shuffle moves small packets from the top onto a new pile, which
+reverses the order of packets. Seven riffles mix a deck of 52 well, a single
+riffle leaves at most two rising sequences.
+*/
+pub mod human_shuffle {
+    use super::rand::Rng;
+    use std::mem;
+
+    // nr of cards in the top half of a cut, Binomial(n, 1/2)
+    fn binomial<R: Rng + ?Sized>(n: usize, rng: &mut R) -> usize {
+        (0..n).filter(|_| rng.gen::<bool>()).count()
+    }
+
+    pub fn cut<T, R: Rng + ?Sized>(cards: &mut [T], rng: &mut R) {
+        let top = binomial(cards.len(), rng);
+        cards.rotate_left(top);
+    }
+
+    pub fn riffle<T, R: Rng + ?Sized>(cards: &mut Vec<T>, rng: &mut R) {
+        let top = binomial(cards.len(), rng);
+        let bottom = cards.split_off(top);
+        let (mut left, mut right) = (cards.len(), bottom.len());
+        let mut top = mem::replace(cards, Vec::with_capacity(left + right)).into_iter();
+        let mut bottom = bottom.into_iter();
+        while left + right > 0 {
+            if rng.gen_range(0, left + right) < left {
+                cards.extend(top.next());
+                left -= 1;
+            } else {
+                cards.extend(bottom.next());
+                right -= 1;
+            }
+        }
+    }
+
+    // packet ends after a card with probability 1/5
+    pub fn overhand<T, R: Rng + ?Sized>(cards: &mut Vec<T>, rng: &mut R) {
+        let mut packets: Vec<Vec<T>> = vec![];
+        let mut packet = vec![];
+        for card in cards.drain(..) {
+            packet.push(card);
+            if rng.gen_range(0, 5) == 0 {
+                packets.push(mem::take(&mut packet));
+            }
+        }
+        packets.push(packet);
+        for packet in packets.into_iter().rev() {
+            cards.extend(packet);
+        }
+    }
+
+    #[cfg(test)]
+    fn rising_sequences(cards: &[usize]) -> usize {
+        let mut position = vec![0; cards.len()];
+        for (i, card) in cards.iter().enumerate() {
+            position[*card] = i;
+        }
+        1 + (1..cards.len())
+            .filter(|card| position[*card] < position[card - 1])
+            .count()
+    }
+
+    #[cfg(test)]
+    mod riffle {
+        use super::super::rand::rngs::StdRng;
+        use super::super::rand::SeedableRng;
+        use super::*;
+
+        #[test]
+        fn at_most_two_rising_sequences() {
+            let mut rng = StdRng::seed_from_u64(1);
+            for _ in 0..100 {
+                let mut deck: Vec<usize> = (0..52).collect();
+                riffle(&mut deck, &mut rng);
+                assert!(rising_sequences(&deck) <= 2);
+                let mut sorted = deck.clone();
+                sorted.sort();
+                assert_eq!(sorted, (0..52).collect::<Vec<usize>>());
+            }
+        }
+
+        #[test]
+        fn seven_riffles_mix_the_deck() {
+            let mut rng = StdRng::seed_from_u64(2);
+            let mut deck: Vec<usize> = (0..52).collect();
+            for _ in 0..7 {
+                riffle(&mut deck, &mut rng);
+            }
+            assert!(rising_sequences(&deck) > 10);
+        }
+    }
+
+    #[cfg(test)]
+    mod overhand {
+        use super::super::rand::rngs::StdRng;
+        use super::super::rand::SeedableRng;
+        use super::*;
+
+        #[test]
+        fn packets_are_reversed() {
+            let mut rng = StdRng::seed_from_u64(3);
+            let mut deck: Vec<usize> = (0..52).collect();
+            overhand(&mut deck, &mut rng);
+            assert_ne!(deck, (0..52).collect::<Vec<usize>>());
+            // inside a packet cards keep their order
+            let packets = 1 + deck.windows(2).filter(|w| w[1] != w[0] + 1).count();
+            assert_eq!(rising_sequences(&deck), packets);
+            let mut sorted = deck.clone();
+            sorted.sort();
+            assert_eq!(sorted, (0..52).collect::<Vec<usize>>());
+        }
+    }
+
+    #[cfg(test)]
+    mod cut {
+        use super::super::rand::rngs::StdRng;
+        use super::super::rand::SeedableRng;
+        use super::*;
+
+        #[test]
+        fn keeps_cyclic_order() {
+            let mut rng = StdRng::seed_from_u64(4);
+            let mut deck: Vec<usize> = (0..52).collect();
+            cut(&mut deck, &mut rng);
+            let first = deck[0];
+            assert!(deck
+                .iter()
+                .enumerate()
+                .all(|(i, card)| *card == (first + i) % 52));
+        }
+    }
+}
+
+impl FaceDown {
+    // nr of face-down cards in a war over the tied card
+    pub fn count(self, tied: &Card) -> usize {
+        match (self, tied.1) {
+            (FaceDown::Cards(nr), _) => nr as usize,
+            (FaceDown::ByRank, Value::Ace) => 1,
+            (FaceDown::ByRank, Value::Jack)
+            | (FaceDown::ByRank, Value::Queen)
+            | (FaceDown::ByRank, Value::King) => 10,
+            (FaceDown::ByRank, Value::Joker) => 0,
+            (FaceDown::ByRank, value) => value as usize + 2, // Two is the first value
+        }
+    }
+}
+
+#[cfg(test)]
+mod face_down {
+    use super::*;
+
+    #[test]
+    fn count_by_rank() {
+        let count = |value| FaceDown::ByRank.count(&Card(Suit::Spade, value, 0));
+        assert_eq!(count(Value::Two), 2);
+        assert_eq!(count(Value::Ten), 10);
+        assert_eq!(count(Value::Queen), 10);
+        assert_eq!(count(Value::Ace), 1);
+        assert_eq!(
+            FaceDown::Cards(3).count(&Card(Suit::Spade, Value::Ace, 0)),
+            3
+        );
+    }
+}
+
+impl Shuffling {
+    pub fn shuffle<T, R: Rng + ?Sized>(self, cards: &mut Vec<T>, rng: &mut R) {
+        match self {
+            Shuffling::Uniform => shuffle(cards, rng),
+            Shuffling::Riffles(nr) => {
+                for _ in 0..nr {
+                    human_shuffle::riffle(cards, rng);
+                }
+                human_shuffle::cut(cards, rng);
+            }
+            Shuffling::Overhand(nr) => {
+                for _ in 0..nr {
+                    human_shuffle::overhand(cards, rng);
+                }
+                human_shuffle::cut(cards, rng);
+            }
+        }
+    }
+}
+
+// N decks shuffled together. Cards of the shoe are unique only together with
+// the tag of their deck.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shoe {
+    pub spec: DeckSpec,
+    pub decks: u8,
+    pub cards: Vec<Card>,
+}
+
+impl Shoe {
+    pub fn new(spec: DeckSpec, decks: u8) -> Self {
+        let cards = (0..decks).flat_map(|nr| make_deck(spec, nr)).collect();
+        Shoe { spec, decks, cards }
+    }
+
+    // The same seed always gives the same shoe, so a game can be replayed.
+    pub fn shuffled(spec: DeckSpec, decks: u8, shuffling: Shuffling, seed: u64) -> Self {
+        use cards::rand::rngs::StdRng;
+        use cards::rand::SeedableRng;
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut shoe = Shoe::new(spec, decks);
+        shuffling.shuffle(&mut shoe.cards, &mut rng);
+        shoe
+    }
+
+    // one card to every player in turn, the first players get one card more
+    // when the shoe doesn't split evenly
+    pub fn deal_round_robin(self, players: usize) -> Vec<Vec<Card>> {
+        let mut hands = vec![vec![]; players];
+        for (nr, card) in self.cards.into_iter().enumerate() {
+            hands[nr % players].push(card);
+        }
+        hands
+    }
+}
+
+#[cfg(test)]
+mod shoe {
     use super::*;
-    use cards::rand::prelude::*;
 
     #[test]
     fn print_rand() {
@@ -287,4 +1339,418 @@ mod shuffled_deck {
         // }
         assert_eq!(x, 52);
     }
+
+    #[test]
+    fn same_seed_same_shoe() {
+        assert_eq!(
+            Shoe::shuffled(DeckSpec::Standard, 2, Shuffling::Uniform, 7),
+            Shoe::shuffled(DeckSpec::Standard, 2, Shuffling::Uniform, 7)
+        );
+    }
+
+    #[test]
+    fn different_seeds_different_shoes() {
+        assert_ne!(
+            Shoe::shuffled(DeckSpec::Standard, 1, Shuffling::Uniform, 7),
+            Shoe::shuffled(DeckSpec::Standard, 1, Shuffling::Uniform, 8)
+        );
+    }
+
+    #[test]
+    fn deal_round_robin() {
+        let shoe = Shoe::shuffled(DeckSpec::Standard, 1, Shuffling::Uniform, 7);
+        let hands = shoe.clone().deal_round_robin(3);
+        let sizes: Vec<usize> = hands.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![18, 17, 17]);
+        assert_eq!(hands[1][..2], [shoe.cards[1], shoe.cards[4]]);
+    }
+
+    #[test]
+    fn shuffled_by_hand() {
+        let new = Shoe::new(DeckSpec::Standard, 1);
+        for shuffling in [Shuffling::Riffles(7), Shuffling::Overhand(3)].iter() {
+            let shoe = Shoe::shuffled(DeckSpec::Standard, 1, *shuffling, 7);
+            assert_ne!(shoe, new);
+            assert_eq!(shoe, Shoe::shuffled(DeckSpec::Standard, 1, *shuffling, 7));
+        }
+        let riffled_once = Shoe::shuffled(DeckSpec::Standard, 1, Shuffling::Riffles(1), 7);
+        assert_ne!(
+            riffled_once,
+            Shoe::shuffled(DeckSpec::Standard, 1, Shuffling::Riffles(7), 7)
+        );
+    }
+
+    #[test]
+    fn copies_differ_by_deck() {
+        let shoe = Shoe::new(DeckSpec::Standard, 2);
+        assert_eq!(shoe.cards.len(), 104);
+        let aces: Vec<&Card> = shoe
+            .cards
+            .iter()
+            .filter(|card| (card.0, card.1) == (Suit::Spade, Value::Ace))
+            .collect();
+        assert_eq!(
+            aces,
+            [
+                &Card(Suit::Spade, Value::Ace, 0),
+                &Card(Suit::Spade, Value::Ace, 1)
+            ]
+        );
+    }
+}
+
+/*
+Short notation of cards: value followed by suit, e.g. "AS", "10H", "Qd" or
+"JkH" (red joker). Ten can be written as "T", suit as a letter or a Unicode
+symbol, and a whole card as a playing card glyph. Deck tag is not part of the
+notation, parsed cards come from deck 0.
+*/
+
+impl fmt::Display for Suit {
+    // {:#} gives Unicode symbol
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (letter, symbol) = match self {
+            Suit::Club => ("C", "♣"),
+            Suit::Diamond => ("D", "♦"),
+            Suit::Heart => ("H", "♥"),
+            Suit::Spade => ("S", "♠"),
+        };
+        f.write_str(if f.alternate() { symbol } else { letter })
+    }
+}
+
+impl FromStr for Suit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "C" | "c" | "♣" | "♧" => Ok(Suit::Club),
+            "D" | "d" | "♦" | "♢" => Ok(Suit::Diamond),
+            "H" | "h" | "♥" | "♡" => Ok(Suit::Heart),
+            "S" | "s" | "♠" | "♤" => Ok(Suit::Spade),
+            _ => Err(format!("unknown suit: {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = match self {
+            Value::Two => "2",
+            Value::Three => "3",
+            Value::Four => "4",
+            Value::Five => "5",
+            Value::Six => "6",
+            Value::Seven => "7",
+            Value::Eight => "8",
+            Value::Nine => "9",
+            Value::Ten => "10",
+            Value::Jack => "J",
+            Value::Queen => "Q",
+            Value::King => "K",
+            Value::Ace => "A",
+            Value::Joker => "Jk",
+        };
+        f.write_str(value)
+    }
+}
+
+impl FromStr for Value {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "2" => Ok(Value::Two),
+            "3" => Ok(Value::Three),
+            "4" => Ok(Value::Four),
+            "5" => Ok(Value::Five),
+            "6" => Ok(Value::Six),
+            "7" => Ok(Value::Seven),
+            "8" => Ok(Value::Eight),
+            "9" => Ok(Value::Nine),
+            "10" | "T" => Ok(Value::Ten),
+            "J" => Ok(Value::Jack),
+            "Q" => Ok(Value::Queen),
+            "K" => Ok(Value::King),
+            "A" => Ok(Value::Ace),
+            "JK" => Ok(Value::Joker),
+            _ => Err(format!("unknown value: {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Card {
+    // {:#} gives value with Unicode suit symbol, e.g. "10♥"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}{:#}", self.1, self.0)
+        } else {
+            write!(f, "{}{}", self.1, self.0)
+        }
+    }
+}
+
+impl Card {
+    // card from the Unicode Playing Cards block, e.g. '🂡' - ace of spades
+    pub fn glyph(&self) -> char {
+        let code = match self {
+            Card(Suit::Heart, Value::Joker, _) => 0x1F0BF,
+            Card(_, Value::Joker, _) => 0x1F0CF,
+            Card(suit, _, _) => {
+                let row = match suit {
+                    Suit::Spade => 0x1F0A0,
+                    Suit::Heart => 0x1F0B0,
+                    Suit::Diamond => 0x1F0C0,
+                    Suit::Club => 0x1F0D0,
+                };
+                // Ace is 1, Knight (0xC) is skipped
+                let column = match self.value() {
+                    14 => 1,
+                    value if value > 11 => value + 1,
+                    value => value,
+                };
+                row + column
+            }
+        };
+        std::char::from_u32(code).unwrap_or('?')
+    }
+
+    fn from_glyph(glyph: char) -> Option<Card> {
+        let code = glyph as u32;
+        match code {
+            0x1F0BF => return Some(RED_JOKER),
+            0x1F0CF => return Some(BLACK_JOKER),
+            _ => (),
+        }
+        let suits = get_suits();
+        let values = get_values();
+        suits
+            .iter()
+            .flat_map(|suit| values[..13].iter().map(move |value| Card(*suit, *value, 0)))
+            .find(|card| card.glyph() as u32 == code)
+    }
+}
+
+impl FromStr for Card {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(glyph), None) => {
+                Card::from_glyph(glyph).ok_or_else(|| format!("unknown card: {:?}", s))
+            }
+            (Some(_), Some(_)) => {
+                let suit_at = s.char_indices().last().map_or(0, |(i, _)| i);
+                let (value, suit) = s.split_at(suit_at);
+                match Card(suit.parse()?, value.parse()?, 0) {
+                    // only the red and the black joker exist
+                    card if card.1 == Value::Joker && card != RED_JOKER && card != BLACK_JOKER => {
+                        Err(format!("unknown joker: {:?}", s))
+                    }
+                    card => Ok(card),
+                }
+            }
+            (None, _) => Err("empty card".to_string()),
+        }
+    }
+}
+
+// cards separated by whitespace or commas, e.g. "AS KH 10D"
+pub fn parse_hand(hand: &str) -> Result<Vec<Card>, String> {
+    hand.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|card| !card.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+#[cfg(test)]
+mod parse_hand {
+    use super::*;
+
+    #[test]
+    fn short_notation() {
+        assert_eq!(
+            parse_hand("AS 10H Qd tc JkH"),
+            Ok(vec![
+                Card(Suit::Spade, Value::Ace, 0),
+                Card(Suit::Heart, Value::Ten, 0),
+                Card(Suit::Diamond, Value::Queen, 0),
+                Card(Suit::Club, Value::Ten, 0),
+                RED_JOKER,
+            ])
+        );
+    }
+
+    #[test]
+    fn unicode_symbols_and_glyphs() {
+        assert_eq!(
+            parse_hand("A♠, 10♡ 🂭 🃏"),
+            Ok(vec![
+                Card(Suit::Spade, Value::Ace, 0),
+                Card(Suit::Heart, Value::Ten, 0),
+                Card(Suit::Spade, Value::Queen, 0),
+                BLACK_JOKER,
+            ])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse_hand("AS 1H"), Err("unknown value: \"1\"".to_string()));
+        assert_eq!(parse_hand("AX"), Err("unknown suit: \"X\"".to_string()));
+        assert_eq!(parse_hand("Z"), Err("unknown card: \"Z\"".to_string()));
+        assert_eq!(parse_hand("JkC"), Err("unknown joker: \"JkC\"".to_string()));
+        assert_eq!(parse_hand("JkD"), Err("unknown joker: \"JkD\"".to_string()));
+        assert_eq!(parse_hand("JkS"), Ok(vec![BLACK_JOKER]));
+        assert_eq!(parse_hand(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for card in make_deck(DeckSpec::Jokers, 0) {
+            assert_eq!(card.to_string().parse(), Ok(card));
+            assert_eq!(format!("{:#}", card).parse(), Ok(card));
+            assert_eq!(card.glyph().to_string().parse(), Ok(card));
+        }
+        let ten = Card(Suit::Heart, Value::Ten, 0);
+        assert_eq!(
+            (ten.to_string(), format!("{:#}", ten), ten.glyph()),
+            ("10H".to_string(), "10♥".to_string(), '🂺')
+        );
+    }
+}
+
+/*
+Compact encoding: index of a card is 13 * suit + value, Club Two is 0 and Spade
+Ace is 51. Red joker is 52, black joker 53.
+*/
+impl Card {
+    pub fn index(&self) -> u8 {
+        match self {
+            Card(Suit::Heart, Value::Joker, _) => 52,
+            Card(_, Value::Joker, _) => 53,
+            Card(suit, _, _) => {
+                let suit = get_suits().iter().position(|s| s == suit).unwrap_or(0);
+                (13 * suit as u32 + self.value() - 2) as u8
+            }
+        }
+    }
+
+    pub fn from_index(index: u8, deck: u8) -> Option<Card> {
+        match index {
+            52 => Some(Card(Suit::Heart, Value::Joker, deck)),
+            53 => Some(Card(Suit::Spade, Value::Joker, deck)),
+            index if index < 52 => {
+                let suit = get_suits()[index as usize / 13];
+                let value = get_values()[index as usize % 13];
+                Some(Card(suit, value, deck))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn cards_to_binary(cards: &[Card]) -> Vec<u8> {
+    cards
+        .iter()
+        .flat_map(|card| [card.index(), card.2])
+        .collect()
+}
+
+pub fn cards_from_binary(bytes: &[u8]) -> Result<Vec<Card>, String> {
+    let cards = bytes.chunks_exact(2);
+    if !cards.remainder().is_empty() {
+        return Err(format!("odd length of binary: {}", bytes.len()));
+    }
+    cards
+        .map(|card| {
+            Card::from_index(card[0], card[1]).ok_or_else(|| format!("wrong index: {}", card[0]))
+        })
+        .collect()
+}
+
+thread_local! {
+    static WIRE_FORMAT: Cell<WireFormat> = const { Cell::new(WireFormat::Terms) };
+}
+
+pub fn wire_format() -> WireFormat {
+    WIRE_FORMAT.with(|format| format.get())
+}
+
+// sets the previous format back when dropped, also when f panics
+struct RestoreWireFormat(WireFormat);
+
+impl Drop for RestoreWireFormat {
+    fn drop(&mut self) {
+        WIRE_FORMAT.with(|current| current.set(self.0));
+    }
+}
+
+// cards are encoded in given format while f runs
+pub fn with_wire_format<T, F: FnOnce() -> T>(format: WireFormat, f: F) -> T {
+    let _restore = RestoreWireFormat(WIRE_FORMAT.with(|current| current.replace(format)));
+    f()
+}
+
+#[cfg(test)]
+mod wire_format {
+    use super::*;
+
+    #[test]
+    fn index_round_trip() {
+        let shoe = Shoe::new(DeckSpec::Jokers, 2);
+        for card in shoe.cards.iter() {
+            assert_eq!(Card::from_index(card.index(), card.2), Some(*card));
+        }
+        let indices: Vec<u8> = make_deck(DeckSpec::Jokers, 0)
+            .iter()
+            .map(Card::index)
+            .collect();
+        assert_eq!(indices, (0..54).collect::<Vec<u8>>());
+        assert_eq!(Card::from_index(54, 0), None);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let cards = vec![
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Spade, Value::Ace, 1),
+            BLACK_JOKER,
+        ];
+        let binary = cards_to_binary(&cards);
+        assert_eq!(binary, vec![0, 0, 51, 1, 53, 0]);
+        assert_eq!(cards_from_binary(&binary), Ok(cards));
+    }
+
+    #[test]
+    fn binary_errors() {
+        assert_eq!(
+            cards_from_binary(&[0, 0, 1]),
+            Err("odd length of binary: 3".to_string())
+        );
+        assert_eq!(
+            cards_from_binary(&[60, 0]),
+            Err("wrong index: 60".to_string())
+        );
+    }
+
+    #[test]
+    fn format_restored_after_call() {
+        assert_eq!(wire_format(), WireFormat::Terms);
+        let inside = with_wire_format(WireFormat::Binary, wire_format);
+        assert_eq!(inside, WireFormat::Binary);
+        assert_eq!(wire_format(), WireFormat::Terms);
+    }
+
+    #[test]
+    fn format_restored_after_panic() {
+        use std::panic;
+
+        let result = panic::catch_unwind(|| {
+            with_wire_format(WireFormat::Binary, || panic!("encoding failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(wire_format(), WireFormat::Terms);
+    }
 }
@@ -1,11 +1,14 @@
 #[macro_use]
 extern crate rustler;
 extern crate rustler_codegen;
+use rustler::resource::ResourceArc;
 use rustler::types::tuple::make_tuple;
 use rustler::{Encoder, Env, NifResult, Term};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 // use rustler::{Encoder, Env, Error, NifResult, Term};
 // use rustler::types::atom::Atom::from_term;
 
+mod audit;
 mod cards;
 mod game;
 mod player;
@@ -13,29 +16,65 @@ mod player;
 mod atoms {
     rustler_atoms! {
         atom card = "Elixir.Card";
+        atom card_rule_beats_all = "Elixir.CardRule.BeatsAll";
+        atom card_rule_beats = "Elixir.CardRule.Beats";
+        atom card_trump_fixed = "Elixir.CardTrump.Fixed";
+        atom card_shuffling_riffles = "Elixir.CardShuffling.Riffles";
+        atom card_shuffling_overhand = "Elixir.CardShuffling.Overhand";
+        atom card_face_down_cards = "Elixir.CardFaceDown.Cards";
 
         atom player_command_add_cards = "Elixir.PlayerRust.CommandAddCards";
+        atom player_command_deal = "Elixir.PlayerRust.CommandDeal";
         atom player_command_remove_cards = "Elixir.PlayerRust.CommandRemoveCards";
+        atom player_command_count_cards = "Elixir.PlayerRust.CommandCountCards";
+        atom player_command_peek_top = "Elixir.PlayerRust.CommandPeekTop";
+        atom player_command_hand_summary = "Elixir.PlayerRust.CommandHandSummary";
+        atom player_command_batch = "Elixir.PlayerRust.CommandBatch";
+        atom player_command_resign = "Elixir.PlayerRust.CommandResign";
+        atom player_command_remove_up_to = "Elixir.PlayerRust.CommandRemoveUpTo";
+        atom player_command_hand_hash = "Elixir.PlayerRust.CommandHandHash";
+
+        atom player_model = "Elixir.PlayerRust.Model";
+        atom hand;
+        atom winnings;
+        atom seed;
+        atom pickup;
+        atom game;
 
         atom player_response_cards_added = "Elixir.PlayerRust.ResponseCardsAdded";
         atom player_response_cards_removed = "Elixir.PlayerRust.ResponseCardsRemoved";
+        atom player_response_cards_removed_after_reshuffle = "Elixir.PlayerRust.ResponseCardsRemovedAfterReshuffle";
         atom player_response_error = "Elixir.PlayerRust.ResponseError";
+        atom player_response_batch = "Elixir.PlayerRust.ResponseBatch";
+        atom player_response_resigned = "Elixir.PlayerRust.ResponseResigned";
+        atom player_response_cards_partially_removed = "Elixir.PlayerRust.ResponseCardsPartiallyRemoved";
+        atom player_response_hand_hash = "Elixir.PlayerRust.ResponseHandHash";
+        atom player_error_duplicated_cards = "Elixir.PlayerRust.ErrorDuplicatedCards";
+        atom player_error_no_cards_to_remove = "Elixir.PlayerRust.ErrorNoCardsToRemove";
+        atom player_error_wrong_game = "Elixir.PlayerRust.ErrorWrongGame";
+        atom player_response_cards_counted = "Elixir.PlayerRust.ResponseCardsCounted";
+        atom player_response_top_cards = "Elixir.PlayerRust.ResponseTopCards";
+        atom player_response_hand_summary = "Elixir.PlayerRust.ResponseHandSummary";
         atom player_response_unable_to_remove_cards = "Elixir.PlayerRust.ResponseUnableToRemoveCards";
 
         atom game_msg_response_from_player = "Elixir.GameRust.MsgResponseFromPlayer";
 
+        atom game_table = "Elixir.GameRust.Table";
+        atom players;
+        atom fighters;
+        atom pile;
+        atom cards;
+        atom responses;
+
         atom game_model_none = "Elixir.GameRust.ModelNone";
         atom game_model_players = "Elixir.GameRust.ModelPlayers";
-        atom game_model_players_with_response = "Elixir.GameRust.ModelPlayersWithResponse";
         atom game_model_battle = "Elixir.GameRust.ModelBattle";
-        atom game_model_battle_with_response = "Elixir.GameRust.ModelBattleWithResponse";
-        atom game_model_battle_won_by_player = "Elixir.GameRust.ModelBattleWonByPlayer";
-        atom game_model_war = "Elixir.GameRust.ModelWar";
-        atom game_model_war_with_response = "Elixir.GameRust.ModelWarWithResponse";
-        atom game_model_war_won_by_player = "Elixir.GameRust.ModelWarWonByPlayer";
-        atom game_model_player1_won = "Elixir.GameRust.ModelPlayer1Won";
-        atom game_model_player2_won = "Elixir.GameRust.ModelPlayer2Won";
         atom game_model_tie = "Elixir.GameRust.ModelTie";
+        atom game_model_adjudication = "Elixir.GameRust.ModelAdjudication";
+        atom game_model_adjudicated = "Elixir.GameRust.ModelAdjudicated";
+        atom game_model_hashing = "Elixir.GameRust.ModelHashing";
+        atom game_model_cycle = "Elixir.GameRust.ModelCycle";
+        atom game_model_player_won = "Elixir.GameRust.ModelPlayerWon";
         atom game_model_error = "Elixir.GameRust.ModelError";
 
         atom game_send_cmds = "Elixir.GameRust.SendCmds";
@@ -48,15 +87,91 @@ rustler_export_nifs! {
     [
         ("player_update", 2, player_update),
         ("game_update", 2, game_update),
+        ("player_update", 3, player_update_with_format),
+        ("game_update", 3, game_update_with_format),
+        ("parse_hand", 1, parse_hand),
+        ("audit", 3, audit),
+        ("player_new", 1, player_new),
+        ("player_model", 1, player_model),
     ],
-    None
+    Some(load)
+}
+
+// Player model kept in Rust between calls, Elixir holds only the handle
+struct PlayerHand(Mutex<player::Model>);
+
+// resource_struct_init! implements the resource type inside of load
+#[allow(non_local_definitions)]
+fn load(env: Env, _info: Term) -> bool {
+    resource_struct_init!(PlayerHand, env);
+    true
+}
+
+// PlayerRust.Model or list of cards (one pile)
+fn decode_player_model(term: Term) -> NifResult<player::Model> {
+    match cards::decode_cards(term) {
+        Ok(cards) => Ok(cards.into()),
+        Err(_) => term.decode(),
+    }
+}
+
+// A model is replaced only after an update returned, so a hand poisoned by a
+// panic still holds the model from before
+fn lock(hand: &PlayerHand) -> MutexGuard<'_, player::Model> {
+    hand.0.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Cards of a PlayerRust.Model, list of cards or handle of a player, the
+// model kept by a handle is not copied
+fn player_cards(term: Term) -> NifResult<Vec<cards::Card>> {
+    match term.decode::<ResourceArc<PlayerHand>>() {
+        Ok(hand) => Ok(lock(&hand).cards()),
+        Err(_) => decode_player_model(term).map(|model| model.cards()),
+    }
+}
+
+// handle of a new player hand, from a model, list of cards or a copy of the
+// hand of another handle
+fn player_new<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let model = match args[0].decode::<ResourceArc<PlayerHand>>() {
+        Ok(hand) => lock(&hand).clone(),
+        Err(_) => decode_player_model(args[0])?,
+    };
+    Ok(ResourceArc::new(PlayerHand(Mutex::new(model))).encode(env))
+}
+
+// copy of the model kept by the handle
+fn player_model<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let hand: ResourceArc<PlayerHand> = args[0].decode()?;
+    let model = lock(&hand);
+    Ok(model.encode(env))
+}
+
+// The handle is returned as the new model, only cards moved by the msg are
+// converted
+fn player_hand_update<'a>(
+    env: Env<'a>,
+    hand: ResourceArc<PlayerHand>,
+    msg: player::Msg,
+) -> NifResult<Term<'a>> {
+    // a copy is updated, the hand keeps its cards if the update panics
+    let cmd = {
+        let mut model = lock(&hand);
+        let (new_model, cmd) = player::update(model.clone(), msg);
+        *model = new_model;
+        cmd
+    };
+    Ok(make_tuple(env, &[hand.encode(env), cmd.encode(env)]))
 }
 
 fn player_update<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
-    let model: player::Model = args[0].decode()?;
+    if let Ok(hand) = args[0].decode::<ResourceArc<PlayerHand>>() {
+        return player_hand_update(env, hand, args[1].decode()?);
+    }
+    let model = decode_player_model(args[0])?;
     let msg: player::Msg = args[1].decode()?;
     let (new_model, cmd) = player::update(model, msg);
-    let result = make_tuple(env, &vec![new_model.encode(env), cmd.encode(env)]);
+    let result = make_tuple(env, &[new_model.encode(env), cmd.encode(env)]);
     Ok(result)
 }
 
@@ -68,3 +183,32 @@ fn game_update<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
     Ok(result)
 }
 
+// The third argument chooses wire format of cards in the result:
+// "WireFormat::Terms" or "WireFormat::Binary". Both are accepted as input.
+fn player_update_with_format<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let format: cards::WireFormat = args[2].decode()?;
+    cards::with_wire_format(format, || player_update(env, &args[..2]))
+}
+
+fn game_update_with_format<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let format: cards::WireFormat = args[2].decode()?;
+    cards::with_wire_format(format, || game_update(env, &args[..2]))
+}
+
+fn parse_hand<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let hand: String = args[0].decode()?;
+    Ok(cards::parse_hand(&hand).encode(env))
+}
+
+// {:ok, nr_of_cards} or {:error, reason}, see audit::check_game
+fn audit<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let model: game::Model = args[0].decode()?;
+    let cmd: game::Cmd = args[1].decode()?;
+    let players: Vec<Term> = args[2].decode()?;
+    let hands = players
+        .into_iter()
+        .map(player_cards)
+        .collect::<NifResult<Vec<_>>>()?;
+    let result = audit::check_game(&model, &cmd, &hands).map_err(|error| error.to_string());
+    Ok(result.encode(env))
+}
@@ -1,27 +1,133 @@
 #![allow(dead_code)]
 
+use rustler::types::elixir_struct::{get_ex_struct_name, make_ex_struct};
 use rustler::types::tuple::{get_tuple, make_tuple};
 use rustler::{Decoder, Encoder, Env, Error, NifResult, Term};
 use rustler_codegen::{NifStruct};
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
 
-use crate::cards::Card;
+use crate::cards::{decode_cards, encode_cards, Card, Suit, Value};
+
+// Where added cards go in the hand, cards are played from the top
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Placement {
+    #[default]
+    Bottom, // won cards are played after the rest of the hand
+    Top,      // won cards are played again at once
+    Winnings, // won cards are kept aside until the hand runs out
+}
+
+impl<'a> Decoder<'a> for Placement {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        match term.decode::<&str>()? {
+            "Placement::Bottom" => Ok(Placement::Bottom),
+            "Placement::Top" => Ok(Placement::Top),
+            "Placement::Winnings" => Ok(Placement::Winnings),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for Placement {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        match self {
+            Placement::Bottom => "Placement::Bottom".encode(env),
+            Placement::Top => "Placement::Top".encode(env),
+            Placement::Winnings => "Placement::Winnings".encode(env),
+        }
+    }
+}
+
+// How won cards are ordered when a player picks them up, see PickupStrategy
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Pickup {
+    #[default]
+    AsReceived,
+    HighFirst,
+    LowFirst,
+    Random,
+    Interleave,
+}
+
+impl<'a> Decoder<'a> for Pickup {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        match term.decode::<&str>()? {
+            "Pickup::AsReceived" => Ok(Pickup::AsReceived),
+            "Pickup::HighFirst" => Ok(Pickup::HighFirst),
+            "Pickup::LowFirst" => Ok(Pickup::LowFirst),
+            "Pickup::Random" => Ok(Pickup::Random),
+            "Pickup::Interleave" => Ok(Pickup::Interleave),
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for Pickup {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        match self {
+            Pickup::AsReceived => "Pickup::AsReceived".encode(env),
+            Pickup::HighFirst => "Pickup::HighFirst".encode(env),
+            Pickup::LowFirst => "Pickup::LowFirst".encode(env),
+            Pickup::Random => "Pickup::Random".encode(env),
+            Pickup::Interleave => "Pickup::Interleave".encode(env),
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
-    AddCards(Vec<Card>), // add list of cards to the model
-    RemoveCards(usize),  // remove nr of cards
+    AddCards(Vec<Card>, Placement), // add list of cards to the model
+    Deal(Vec<Card>, u64),           // cards dealt into the hand, seed of the player
+    RemoveCards(usize),             // remove nr of cards
+    RemoveUpTo(usize),              // remove nr of cards or the rest of them
+    CountCards,                     // nr of cards, winnings included
+    PeekTop(usize),                 // look at nr of cards from the top of the hand
+    HandSummary,                    // nr of cards of every value and suit
+    Batch(Vec<Command>),            // commands in order, all or none change the model
+    Resign,                         // leave the game which dealt the cards
+    HandHash,                       // hash of the piles and the seed
 }
 
 impl<'a> Decoder<'a> for Command {
     fn decode(term: Term<'a>) -> NifResult<Self> {
+        let env = term.get_env();
+        if term == ::atoms::player_command_count_cards().encode(env) {
+            return Ok(Command::CountCards);
+        } else if term == ::atoms::player_command_hand_summary().encode(env) {
+            return Ok(Command::HandSummary);
+        } else if term == ::atoms::player_command_resign().encode(env) {
+            return Ok(Command::Resign);
+        } else if term == ::atoms::player_command_hand_hash().encode(env) {
+            return Ok(Command::HandHash);
+        }
         let terms = get_tuple(term)?;
         match terms.as_slice() {
-            [command, arg] => {
+            [command, cards, placement] => {
                 let env = term.get_env();
                 if *command == ::atoms::player_command_add_cards().encode(env) {
-                    Ok(Command::AddCards(arg.decode()?))
-                } else if *command == ::atoms::player_command_remove_cards().encode(env) {
+                    Ok(Command::AddCards(
+                        decode_cards(*cards)?,
+                        placement.decode()?,
+                    ))
+                } else if *command == ::atoms::player_command_deal().encode(env) {
+                    Ok(Command::Deal(decode_cards(*cards)?, placement.decode()?))
+                } else {
+                    Err(Error::BadArg)
+                }
+            }
+            [command, arg] => {
+                let env = term.get_env();
+                if *command == ::atoms::player_command_remove_cards().encode(env) {
                     Ok(Command::RemoveCards(arg.decode()?))
+                } else if *command == ::atoms::player_command_remove_up_to().encode(env) {
+                    Ok(Command::RemoveUpTo(arg.decode()?))
+                } else if *command == ::atoms::player_command_peek_top().encode(env) {
+                    Ok(Command::PeekTop(arg.decode()?))
+                } else if *command == ::atoms::player_command_batch().encode(env) {
+                    Ok(Command::Batch(arg.decode()?))
                 } else {
                     Err(Error::BadArg)
                 }
@@ -34,13 +140,22 @@ impl<'a> Decoder<'a> for Command {
 impl Encoder for Command {
     fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
         match self {
-            Command::AddCards(cards) => {
+            Command::AddCards(cards, placement) => {
                 let add_cards = vec![
                     ::atoms::player_command_add_cards().encode(env),
-                    cards.encode(env),
+                    encode_cards(cards, env),
+                    placement.encode(env),
                 ];
                 make_tuple(env, &add_cards)
             }
+            Command::Deal(cards, seed) => {
+                let deal = vec![
+                    ::atoms::player_command_deal().encode(env),
+                    encode_cards(cards, env),
+                    seed.encode(env),
+                ];
+                make_tuple(env, &deal)
+            }
             Command::RemoveCards(nr) => {
                 let remove_cards = vec![
                     ::atoms::player_command_remove_cards().encode(env),
@@ -48,6 +163,31 @@ impl Encoder for Command {
                 ];
                 make_tuple(env, &remove_cards)
             }
+            Command::RemoveUpTo(nr) => {
+                let remove_up_to = vec![
+                    ::atoms::player_command_remove_up_to().encode(env),
+                    nr.encode(env),
+                ];
+                make_tuple(env, &remove_up_to)
+            }
+            Command::CountCards => ::atoms::player_command_count_cards().encode(env),
+            Command::PeekTop(nr) => {
+                let peek_top = vec![
+                    ::atoms::player_command_peek_top().encode(env),
+                    nr.encode(env),
+                ];
+                make_tuple(env, &peek_top)
+            }
+            Command::HandSummary => ::atoms::player_command_hand_summary().encode(env),
+            Command::Batch(commands) => {
+                let batch = vec![
+                    ::atoms::player_command_batch().encode(env),
+                    commands.encode(env),
+                ];
+                make_tuple(env, &batch)
+            }
+            Command::Resign => ::atoms::player_command_resign().encode(env),
+            Command::HandHash => ::atoms::player_command_hand_hash().encode(env),
         }
     }
 }
@@ -67,6 +207,73 @@ sent by Elixir
 pub struct Msg {
     pub from: Vec<u8>,
     pub command: Command,
+    pub id: u64, // round of the game, echoed in Cmd
+}
+
+/*
+Nr of cards of a player for every value and suit, only values and suits held
+are listed. Jokers have no suit.
+*/
+#[derive(NifStruct)]
+#[module = "PlayerRust.HandSummary"]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HandSummary {
+    pub values: Vec<(Value, usize)>,
+    pub suits: Vec<(Suit, usize)>,
+}
+
+/*
+Commands refused by a player, the model is not changed
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerError {
+    DuplicatedCards(Vec<Card>), // cards already held or sent twice
+    NoCardsToRemove,            // RemoveCards(0)
+    WrongGame(Vec<u8>),         // sender is not the game which dealt the cards
+}
+
+impl<'a> Decoder<'a> for PlayerError {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        let env = term.get_env();
+        if term == ::atoms::player_error_no_cards_to_remove().encode(env) {
+            return Ok(PlayerError::NoCardsToRemove);
+        }
+        let terms = get_tuple(term)?;
+        match terms.as_slice() {
+            [error, arg] => {
+                if *error == ::atoms::player_error_duplicated_cards().encode(env) {
+                    Ok(PlayerError::DuplicatedCards(decode_cards(*arg)?))
+                } else if *error == ::atoms::player_error_wrong_game().encode(env) {
+                    Ok(PlayerError::WrongGame(arg.decode()?))
+                } else {
+                    Err(Error::BadArg)
+                }
+            }
+            _ => Err(Error::BadArg),
+        }
+    }
+}
+
+impl Encoder for PlayerError {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        match self {
+            PlayerError::DuplicatedCards(cards) => {
+                let duplicated_cards = vec![
+                    ::atoms::player_error_duplicated_cards().encode(env),
+                    encode_cards(cards, env),
+                ];
+                make_tuple(env, &duplicated_cards)
+            }
+            PlayerError::NoCardsToRemove => ::atoms::player_error_no_cards_to_remove().encode(env),
+            PlayerError::WrongGame(sender) => {
+                let wrong_game = vec![
+                    ::atoms::player_error_wrong_game().encode(env),
+                    sender.encode(env),
+                ];
+                make_tuple(env, &wrong_game)
+            }
+        }
+    }
 }
 
 /*
@@ -77,12 +284,23 @@ Response from update function - Cmd to be executed by runtime
 pub enum Response {
     CardsAdded(usize),
     CardsRemoved(Vec<Card>),
-    UnableToRemoveCards(usize), // usize - nr of cards
-    Error(String),
+    CardsRemovedAfterReshuffle(Vec<Card>), // winnings were shuffled into the hand
+    UnableToRemoveCards(usize),            // usize - nr of cards
+    CardsPartiallyRemoved(Vec<Card>),      // fewer than asked by RemoveUpTo, the rest
+    CardsCounted(usize),
+    TopCards(Vec<Card>), // from the bottom up, like CardsRemoved
+    HandSummary(HandSummary),
+    Error(PlayerError),
+    Batch(Vec<Response>), // a response for every command of the batch
+    Resigned,             // sent to the game which dealt the cards
+    HandHash(u64),
 }
 
 impl<'a> Decoder<'a> for Response {
     fn decode(term: Term<'a>) -> NifResult<Self> {
+        if term == ::atoms::player_response_resigned().encode(term.get_env()) {
+            return Ok(Response::Resigned);
+        }
         let terms = get_tuple(term)?;
         match terms.as_slice() {
             [command, arg] => {
@@ -90,11 +308,28 @@ impl<'a> Decoder<'a> for Response {
                 if *command == ::atoms::player_response_cards_added().encode(env) {
                     Ok(Response::CardsAdded(arg.decode()?))
                 } else if *command == ::atoms::player_response_cards_removed().encode(env) {
-                    Ok(Response::CardsRemoved(arg.decode()?))
+                    Ok(Response::CardsRemoved(decode_cards(*arg)?))
+                } else if *command
+                    == ::atoms::player_response_cards_removed_after_reshuffle().encode(env)
+                {
+                    Ok(Response::CardsRemovedAfterReshuffle(decode_cards(*arg)?))
                 } else if *command == ::atoms::player_response_unable_to_remove_cards().encode(env) {
                     Ok(Response::UnableToRemoveCards(arg.decode()?))
+                } else if *command == ::atoms::player_response_cards_partially_removed().encode(env)
+                {
+                    Ok(Response::CardsPartiallyRemoved(decode_cards(*arg)?))
+                } else if *command == ::atoms::player_response_cards_counted().encode(env) {
+                    Ok(Response::CardsCounted(arg.decode()?))
+                } else if *command == ::atoms::player_response_top_cards().encode(env) {
+                    Ok(Response::TopCards(decode_cards(*arg)?))
+                } else if *command == ::atoms::player_response_hand_summary().encode(env) {
+                    Ok(Response::HandSummary(arg.decode()?))
                 } else if *command == ::atoms::player_response_error().encode(env) {
                     Ok(Response::Error(arg.decode()?))
+                } else if *command == ::atoms::player_response_batch().encode(env) {
+                    Ok(Response::Batch(arg.decode()?))
+                } else if *command == ::atoms::player_response_hand_hash().encode(env) {
+                    Ok(Response::HandHash(arg.decode()?))
                 } else {
                     Err(Error::BadArg)
                 }
@@ -117,7 +352,14 @@ impl Encoder for Response {
             Response::CardsRemoved(cards) => {
                 let cards_removed = vec![
                     ::atoms::player_response_cards_removed().encode(env),
-                    cards.encode(env),
+                    encode_cards(cards, env),
+                ];
+                make_tuple(env, &cards_removed)
+            }
+            Response::CardsRemovedAfterReshuffle(cards) => {
+                let cards_removed = vec![
+                    ::atoms::player_response_cards_removed_after_reshuffle().encode(env),
+                    encode_cards(cards, env),
                 ];
                 make_tuple(env, &cards_removed)
             }
@@ -128,6 +370,34 @@ impl Encoder for Response {
                 ];
                 make_tuple(env, &unable_to_remove_cards)
             }
+            Response::CardsPartiallyRemoved(cards) => {
+                let cards_removed = vec![
+                    ::atoms::player_response_cards_partially_removed().encode(env),
+                    encode_cards(cards, env),
+                ];
+                make_tuple(env, &cards_removed)
+            }
+            Response::CardsCounted(nr) => {
+                let cards_counted = vec![
+                    ::atoms::player_response_cards_counted().encode(env),
+                    nr.encode(env),
+                ];
+                make_tuple(env, &cards_counted)
+            }
+            Response::TopCards(cards) => {
+                let top_cards = vec![
+                    ::atoms::player_response_top_cards().encode(env),
+                    encode_cards(cards, env),
+                ];
+                make_tuple(env, &top_cards)
+            }
+            Response::HandSummary(summary) => {
+                let hand_summary = vec![
+                    ::atoms::player_response_hand_summary().encode(env),
+                    summary.encode(env),
+                ];
+                make_tuple(env, &hand_summary)
+            }
             Response::Error(err) => {
                 let error = vec![
                     ::atoms::player_response_error().encode(env),
@@ -135,6 +405,21 @@ impl Encoder for Response {
                 ];
                 make_tuple(env, &error)
             }
+            Response::Batch(responses) => {
+                let batch = vec![
+                    ::atoms::player_response_batch().encode(env),
+                    responses.encode(env),
+                ];
+                make_tuple(env, &batch)
+            }
+            Response::Resigned => ::atoms::player_response_resigned().encode(env),
+            Response::HandHash(hash) => {
+                let hand_hash = vec![
+                    ::atoms::player_response_hand_hash().encode(env),
+                    hash.encode(env),
+                ];
+                make_tuple(env, &hand_hash)
+            }
         }
     }
 }
@@ -150,178 +435,1384 @@ Cmd will be sent to Elixir and executed
 pub struct Cmd {
     pub game: Vec<u8>,
     pub response: Response,
+    pub id: u64, // id of the msg
 }
 
-pub type Model = Vec<Card>;
+/*
+Model of a player: the hand (draw pile) and won cards kept aside when the game
+is played with Placement::Winnings. Game is the sender of the first cards, only
+it may add or remove cards later. When the hand runs out the winnings are
+shuffled with the seed, the next seed is kept for the next reshuffle (or
+Pickup::Random). Pickup is the strategy of the player, chosen in the initial
+model. A list of cards is accepted as a model with one pile.
+*/
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Model {
+    pub hand: VecDeque<Card>, // the back is the top of the hand
+    pub winnings: Vec<Card>,
+    pub seed: u64,
+    pub pickup: Pickup,
+    pub game: Option<Vec<u8>>,
+    pub held: Held, // index of hand and winnings, see Model::indexed
+}
 
-pub fn update(model: Model, msg: Msg) -> (Model, Cmd) {
-    match (model, msg) {
-        (
-            cards,
-            Msg {
-                from: sender,
-                command: Command::AddCards(cards_to_add),
-            },
-        ) => {
-            // let () = cards;
-            let mut new_cards = cards.to_vec();
-            let mut cards_received = cards_to_add.to_vec();
-            new_cards.append(&mut cards_received);
-            (
-                new_cards,
-                Cmd {
-                    game: sender,
-                    response: Response::CardsAdded(cards_to_add.len()),
-                },
-            )
+impl<'a> Decoder<'a> for Model {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        let env = term.get_env();
+        if get_ex_struct_name(term)? != ::atoms::player_model() {
+            return Err(Error::BadArg);
         }
-        (
-            cards,
-            Msg {
-                from: sender,
-                command: Command::RemoveCards(nr),
-            },
-        ) => {
-            // let () = nr;
-            let cards = cards.to_vec();
-            let nr_cards = nr;
-            // Ok to remove cards
-            if cards.len() >= nr_cards {
-                let mut cards_left = cards.to_vec();
-                let length = cards_left.len();
-                let cards_to_send = cards_left.split_off(length - nr_cards);
-                //let () = cards_left;
-                (
-                    cards_left,
-                    Cmd {
-                        game: sender,
-                        response: Response::CardsRemoved(cards_to_send.to_vec()),
-                    },
-                )
-            } else {
-                (
-                    cards,
-                    Cmd {
-                        game: sender,
-                        response: Response::UnableToRemoveCards(nr_cards),
-                    },
+        let model = Model {
+            hand: decode_cards(term.map_get(::atoms::hand().encode(env))?)?.into(),
+            winnings: decode_cards(term.map_get(::atoms::winnings().encode(env))?)?,
+            seed: term.map_get(::atoms::seed().encode(env))?.decode()?,
+            pickup: term.map_get(::atoms::pickup().encode(env))?.decode()?,
+            game: term.map_get(::atoms::game().encode(env))?.decode()?,
+            held: Held::default(),
+        };
+        Ok(model.indexed())
+    }
+}
+
+impl Encoder for Model {
+    fn encode<'b>(&self, env: Env<'b>) -> Term<'b> {
+        let hand: Vec<Card> = self.hand.iter().cloned().collect();
+        let model = make_ex_struct(env, "Elixir.PlayerRust.Model")
+            .and_then(|map| map.map_put(::atoms::hand().encode(env), encode_cards(&hand, env)))
+            .and_then(|map| {
+                map.map_put(
+                    ::atoms::winnings().encode(env),
+                    encode_cards(&self.winnings, env),
                 )
-            }
+            })
+            .and_then(|map| map.map_put(::atoms::seed().encode(env), self.seed.encode(env)))
+            .and_then(|map| map.map_put(::atoms::pickup().encode(env), self.pickup.encode(env)))
+            .and_then(|map| map.map_put(::atoms::game().encode(env), self.game.encode(env)));
+        match model {
+            Ok(model) => model,
+            Err(_) => unreachable!("keys of PlayerRust.Model are atoms"),
+        }
+    }
+}
+
+// Cards held by a player, a bit for every card index per deck tag. Checking
+// cards added to the model doesn't depend on the size of the hand.
+#[derive(Debug, Clone, Default)]
+pub struct Held(Vec<u64>);
+
+impl Held {
+    fn contains(&self, card: &Card) -> bool {
+        self.0
+            .get(card.2 as usize)
+            .is_some_and(|bits| bits & (1 << card.index()) != 0)
+    }
+
+    fn insert(&mut self, card: &Card) {
+        let deck = card.2 as usize;
+        if self.0.len() <= deck {
+            self.0.resize(deck + 1, 0);
+        }
+        self.0[deck] |= 1 << card.index();
+    }
+
+    fn remove(&mut self, card: &Card) {
+        if let Some(bits) = self.0.get_mut(card.2 as usize) {
+            *bits &= !(1 << card.index());
+        }
+    }
+}
+
+// decks without cards don't matter
+impl PartialEq for Held {
+    fn eq(&self, other: &Held) -> bool {
+        let decks = self.0.len().max(other.0.len());
+        (0..decks).all(|deck| self.0.get(deck).unwrap_or(&0) == other.0.get(deck).unwrap_or(&0))
+    }
+}
+
+impl From<Vec<Card>> for Model {
+    fn from(cards: Vec<Card>) -> Self {
+        Model {
+            hand: cards.into(),
+            ..Model::default()
+        }
+        .indexed()
+    }
+}
+
+impl Model {
+    // the index of held cards is rebuilt, call it after the piles were set
+    pub fn indexed(mut self) -> Model {
+        let mut held = Held::default();
+        for card in self.hand.iter().chain(self.winnings.iter()) {
+            held.insert(card);
+        }
+        self.held = held;
+        self
+    }
+
+    fn len(&self) -> usize {
+        self.hand.len() + self.winnings.len()
+    }
+
+    // hand from the top down, then winnings
+    pub fn cards(&self) -> Vec<Card> {
+        self.hand
+            .iter()
+            .rev()
+            .chain(self.winnings.iter())
+            .cloned()
+            .collect()
+    }
+
+    // the same for the same cards in the same order in both piles and the same
+    // seed, so a game in a cycle gets the same hashes again
+    fn hand_hash(&self) -> u64 {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        self.hand.len().hash(&mut hasher);
+        for card in self.hand.iter().chain(self.winnings.iter()) {
+            (card.index(), card.2).hash(&mut hasher);
+        }
+        self.seed.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // shuffled winnings go under the rest of the hand
+    fn reshuffle(&mut self) {
+        use crate::cards::shuffle_seeded;
+        self.seed = shuffle_seeded(&mut self.winnings, self.seed);
+        for card in self.winnings.drain(..).rev() {
+            self.hand.push_front(card);
+        }
+    }
+}
+
+/*
+Pickup strategy orders won cards before they go into the hand. Cards are listed
+from the bottom up, like the hand: the last one is played first. The seed of
+the model is used and advanced by strategies which need randomness.
+*/
+pub trait PickupStrategy {
+    fn order(&self, cards: &mut Vec<Card>, seed: &mut u64);
+}
+
+// Cards in the order sent by the game: pile, then cards of the players
+pub struct AsReceived;
+
+impl PickupStrategy for AsReceived {
+    fn order(&self, _cards: &mut Vec<Card>, _seed: &mut u64) {}
+}
+
+// The highest card is played first. Cards are sorted by face value, Ace high:
+// a player doesn't know the ranking, trump or rules of the game, so under
+// Ranking::AceLow or Ranking::Peace it is not the strongest card.
+pub struct HighFirst;
+
+impl PickupStrategy for HighFirst {
+    fn order(&self, cards: &mut Vec<Card>, _seed: &mut u64) {
+        cards.sort_by_key(|card| card.value());
+    }
+}
+
+// The lowest card is played first, by face value like HighFirst
+pub struct LowFirst;
+
+impl PickupStrategy for LowFirst {
+    fn order(&self, cards: &mut Vec<Card>, _seed: &mut u64) {
+        cards.sort_by_key(|card| Reverse(card.value()));
+    }
+}
+
+pub struct Random;
+
+impl PickupStrategy for Random {
+    fn order(&self, cards: &mut Vec<Card>, seed: &mut u64) {
+        use crate::cards::shuffle_seeded;
+        *seed = shuffle_seeded(cards, *seed);
+    }
+}
+
+// Cards of the first half alternate with cards of the second half, in a battle
+// these are cards of the two players
+pub struct Interleave;
+
+impl PickupStrategy for Interleave {
+    fn order(&self, cards: &mut Vec<Card>, _seed: &mut u64) {
+        let second = cards.split_off(cards.len().div_ceil(2));
+        let first = mem::take(cards);
+        let mut second = second.into_iter();
+        for card in first {
+            cards.push(card);
+            cards.extend(second.next());
+        }
+    }
+}
+
+impl Pickup {
+    pub fn strategy(self) -> &'static dyn PickupStrategy {
+        match self {
+            Pickup::AsReceived => &AsReceived,
+            Pickup::HighFirst => &HighFirst,
+            Pickup::LowFirst => &LowFirst,
+            Pickup::Random => &Random,
+            Pickup::Interleave => &Interleave,
         }
     }
 }
 
 #[cfg(test)]
-mod update {
+mod pickup {
     use super::*;
-    use crate::cards::Suit;
-    use crate::cards::Value;
+    use crate::cards::{Suit, Value};
+
+    fn won() -> Vec<Card> {
+        vec![
+            Card(Suit::Club, Value::Five, 0),
+            Card(Suit::Club, Value::Ace, 0),
+            Card(Suit::Heart, Value::Two, 0),
+            Card(Suit::Heart, Value::King, 0),
+        ]
+    }
+
+    fn ordered(pickup: Pickup, seed: &mut u64) -> Vec<Card> {
+        let mut cards = won();
+        pickup.strategy().order(&mut cards, seed);
+        cards
+    }
 
     #[test]
-    fn add_card_to_empty_model() {
-        let model = Vec::new();
-        let card = Card(Suit::Heart, Value::Ace);
-        let msg = Msg {
-            from: vec![0],
-            command: Command::AddCards(vec![card]),
-        };
-        let (updated_model, _cmd) = update(model, msg);
-        assert_eq!(updated_model, vec![card]);
+    fn high_and_low_first() {
+        let high_first = ordered(Pickup::HighFirst, &mut 0);
+        assert_eq!(high_first.last(), Some(&Card(Suit::Club, Value::Ace, 0)));
+        assert_eq!(high_first[0], Card(Suit::Heart, Value::Two, 0));
+        let mut low_first = ordered(Pickup::LowFirst, &mut 0);
+        low_first.reverse();
+        assert_eq!(low_first, high_first);
+        assert_eq!(ordered(Pickup::AsReceived, &mut 0), won());
     }
 
     #[test]
-    fn add_cards_to_model() {
-        let model = vec![
-            Card(Suit::Club, Value::Two),
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Diamond, Value::Ace),
-        ];
-        let msg = Msg {
-            from: vec![0],
-            command: Command::AddCards(vec![
-                Card(Suit::Heart, Value::Two),
-                Card(Suit::Heart, Value::Three),
-                Card(Suit::Spade, Value::Ace),
-            ]),
-        };
-        let (updated_model, _cmd) = update(model, msg);
+    fn interleave() {
         assert_eq!(
-            updated_model,
+            ordered(Pickup::Interleave, &mut 0),
             vec![
-                Card(Suit::Club, Value::Two),
-                Card(Suit::Club, Value::Three),
-                Card(Suit::Diamond, Value::Ace),
-                Card(Suit::Heart, Value::Two),
-                Card(Suit::Heart, Value::Three),
-                Card(Suit::Spade, Value::Ace),
+                Card(Suit::Club, Value::Five, 0),
+                Card(Suit::Heart, Value::Two, 0),
+                Card(Suit::Club, Value::Ace, 0),
+                Card(Suit::Heart, Value::King, 0),
             ]
         );
+        let mut odd = won();
+        odd.pop();
+        Interleave.order(&mut odd, &mut 0);
+        assert_eq!(odd.len(), 3);
     }
 
     #[test]
-    fn remove_cards_success() {
-        let model = vec![
-            Card(Suit::Club, Value::Two),
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Diamond, Value::Ace),
-            Card(Suit::Heart, Value::Two),
-            Card(Suit::Heart, Value::Three),
-            Card(Suit::Spade, Value::Ace),
+    fn random_is_seeded() {
+        let (mut seed1, mut seed2) = (7, 7);
+        let random = ordered(Pickup::Random, &mut seed1);
+        assert_eq!(random, ordered(Pickup::Random, &mut seed2));
+        assert_ne!(seed1, 7);
+        let mut sorted = random.clone();
+        sorted.sort_by_key(|card| card.index());
+        let mut expected = won();
+        expected.sort_by_key(|card| card.index());
+        assert_eq!(sorted, expected);
+    }
+}
+
+// nr of cards of every value (from the lowest) and suit
+fn hand_summary(cards: &[Card]) -> HandSummary {
+    fn count<K: PartialEq>(counts: &mut Vec<(K, usize)>, key: K) {
+        match counts.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, nr)) => *nr += 1,
+            None => counts.push((key, 1)),
+        }
+    }
+    let mut cards = cards.to_vec();
+    cards.sort_by_key(|card| (card.value(), card.index()));
+    let mut summary = HandSummary::default();
+    for card in &cards {
+        count(&mut summary.values, card.1);
+    }
+    cards.sort_by_key(|card| card.index());
+    for card in cards.iter().filter(|card| !card.is_joker()) {
+        count(&mut summary.suits, card.0);
+    }
+    summary
+}
+
+#[cfg(test)]
+mod hand_summary {
+    use super::*;
+
+    #[test]
+    fn counts_values_and_suits() {
+        let cards = vec![
+            Card(Suit::Spade, Value::Ace, 0),
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Heart, Value::Joker, 0),
+            Card(Suit::Spade, Value::Two, 1),
+            Card(Suit::Spade, Value::Ace, 1),
         ];
-        let msg = Msg {
-            from: vec![0],
-            command: Command::RemoveCards(2),
-        };
-        let (updated_model, cmd) = update(model, msg);
         assert_eq!(
-            (updated_model, cmd),
-            (
-                vec![
-                    Card(Suit::Club, Value::Two),
-                    Card(Suit::Club, Value::Three),
-                    Card(Suit::Diamond, Value::Ace),
-                    Card(Suit::Heart, Value::Two),
-                ],
-                Cmd {
-                    game: vec![0],
-                    response: Response::CardsRemoved(vec![
-                        Card(Suit::Heart, Value::Three),
-                        Card(Suit::Spade, Value::Ace),
-                    ])
-                }
-            )
+            hand_summary(&cards),
+            HandSummary {
+                values: vec![(Value::Two, 2), (Value::Ace, 2), (Value::Joker, 1)],
+                suits: vec![(Suit::Club, 1), (Suit::Spade, 3)],
+            }
         );
+        assert_eq!(hand_summary(&[]), HandSummary::default());
     }
+}
 
-    #[test]
-    fn remove_cards_failure() {
-        let model = vec![
-            Card(Suit::Club, Value::Two),
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Diamond, Value::Ace),
-            Card(Suit::Heart, Value::Two),
-            Card(Suit::Heart, Value::Three),
-            Card(Suit::Spade, Value::Ace),
-        ];
+impl Response {
+    // game does not care where cards of a player came from
+    pub fn without_reshuffle(self) -> Response {
+        match self {
+            Response::CardsRemovedAfterReshuffle(cards) => Response::CardsRemoved(cards),
+            response => response,
+        }
+    }
+
+    // The game acts on the last response of a batch. A batch which would fail
+    // is refused with the failing response, so the others (e.g. CardsAdded
+    // for won cards) succeeded and need no check.
+    pub fn unbatched(self) -> Response {
+        match self {
+            Response::Batch(mut responses) => match responses.pop() {
+                Some(response) => response.unbatched(),
+                None => Response::Batch(responses),
+            },
+            response => response,
+        }
+    }
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlayerError::DuplicatedCards(cards) => {
+                let cards: Vec<String> = cards.iter().map(|card| card.to_string()).collect();
+                write!(f, "cards already held: {}", cards.join(" "))
+            }
+            PlayerError::NoCardsToRemove => write!(f, "no cards to remove"),
+            PlayerError::WrongGame(sender) => write!(f, "cards not dealt by {:?}", sender),
+        }
+    }
+}
+
+// Commands which change the model are checked, queries are answered to anyone
+fn validate(model: &Model, from: &[u8], command: &Command) -> Result<(), PlayerError> {
+    match (command, &model.game) {
+        (Command::AddCards(_, _), Some(game))
+        | (Command::Deal(_, _), Some(game))
+        | (Command::RemoveCards(_), Some(game))
+        | (Command::RemoveUpTo(_), Some(game))
+            if *game != from =>
+        {
+            Err(PlayerError::WrongGame(from.to_vec()))
+        }
+        (Command::RemoveCards(0), _) | (Command::RemoveUpTo(0), _) => {
+            Err(PlayerError::NoCardsToRemove)
+        }
+        (Command::AddCards(cards, _), _) | (Command::Deal(cards, _), _) => {
+            let mut added = Held::default();
+            let mut duplicated = vec![];
+            for card in cards {
+                if model.held.contains(card) || added.contains(card) {
+                    duplicated.push(*card);
+                } else {
+                    added.insert(card);
+                }
+            }
+            if duplicated.is_empty() {
+                Ok(())
+            } else {
+                Err(PlayerError::DuplicatedCards(duplicated))
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
+/*
+The response of the first command of a batch which would fail, so nothing is
+done. Commands are validated against the model, len is the nr of cards after
+the commands before and added the cards they added. Cards removed by the batch
+count as held: a game can't send them back before it got them.
+*/
+fn refused(
+    model: &Model,
+    from: &[u8],
+    commands: &[Command],
+    len: &mut usize,
+    added: &mut Held,
+) -> Option<Response> {
+    for command in commands {
+        if let Err(error) = validate(model, from, command) {
+            return Some(Response::Error(error));
+        }
+        match command {
+            Command::AddCards(cards, _) | Command::Deal(cards, _) => {
+                let duplicated: Vec<Card> = cards
+                    .iter()
+                    .filter(|card| added.contains(card))
+                    .cloned()
+                    .collect();
+                if !duplicated.is_empty() {
+                    return Some(Response::Error(PlayerError::DuplicatedCards(duplicated)));
+                }
+                for card in cards {
+                    added.insert(card);
+                }
+                *len += cards.len();
+            }
+            Command::RemoveCards(nr) if *len < *nr => {
+                return Some(Response::UnableToRemoveCards(*nr))
+            }
+            Command::RemoveUpTo(nr) if *len == 0 => {
+                return Some(Response::UnableToRemoveCards(*nr))
+            }
+            Command::RemoveCards(nr) | Command::RemoveUpTo(nr) => *len -= (*len).min(*nr),
+            Command::Batch(commands) => {
+                if let Some(response) = refused(model, from, commands, len, added) {
+                    return Some(Response::Batch(vec![response]));
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+pub fn update(model: Model, msg: Msg) -> (Model, Cmd) {
+    if let Err(error) = validate(&model, &msg.from, &msg.command) {
+        return (
+            model,
+            Cmd {
+                game: msg.from,
+                response: Response::Error(error),
+                id: msg.id,
+            },
+        );
+    }
+    let id = msg.id;
+    match (model, msg) {
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::AddCards(cards_to_add, placement),
+                ..
+            },
+        ) => {
+            let mut new_model = model;
+            new_model.game.get_or_insert_with(|| sender.clone());
+            let mut cards_to_add = cards_to_add;
+            for card in &cards_to_add {
+                new_model.held.insert(card);
+            }
+            let strategy = new_model.pickup.strategy();
+            strategy.order(&mut cards_to_add, &mut new_model.seed);
+            match placement {
+                Placement::Bottom => {
+                    for card in cards_to_add.iter().rev() {
+                        new_model.hand.push_front(*card);
+                    }
+                }
+                Placement::Top => new_model.hand.extend(cards_to_add.iter()),
+                Placement::Winnings => new_model.winnings.extend(cards_to_add.iter()),
+            }
+            (
+                new_model,
+                Cmd {
+                    game: sender,
+                    response: Response::CardsAdded(cards_to_add.len()),
+                    id,
+                },
+            )
+        }
+        // the seed of the player is set with the deal, the pickup strategy is
+        // for won cards only
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::Deal(cards, seed),
+                ..
+            },
+        ) => {
+            let mut new_model = Model { seed, ..model };
+            new_model.game.get_or_insert_with(|| sender.clone());
+            for card in cards.iter().rev() {
+                new_model.held.insert(card);
+                new_model.hand.push_front(*card);
+            }
+            (
+                new_model,
+                Cmd {
+                    game: sender,
+                    response: Response::CardsAdded(cards.len()),
+                    id,
+                },
+            )
+        }
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::RemoveCards(nr),
+                ..
+            },
+        ) => {
+            // let () = nr;
+            let nr_cards = nr;
+            // Ok to remove cards
+            if model.len() >= nr_cards {
+                let mut model_left = model;
+                let reshuffled = model_left.hand.len() < nr_cards;
+                if reshuffled {
+                    model_left.reshuffle();
+                }
+                let length = model_left.hand.len();
+                let cards_to_send = Vec::from(model_left.hand.split_off(length - nr_cards));
+                for card in &cards_to_send {
+                    model_left.held.remove(card);
+                }
+                let response = if reshuffled {
+                    Response::CardsRemovedAfterReshuffle(cards_to_send)
+                } else {
+                    Response::CardsRemoved(cards_to_send)
+                };
+                (
+                    model_left,
+                    Cmd {
+                        game: sender,
+                        response,
+                        id,
+                    },
+                )
+            } else {
+                (
+                    model,
+                    Cmd {
+                        game: sender,
+                        response: Response::UnableToRemoveCards(nr_cards),
+                        id,
+                    },
+                )
+            }
+        }
+        // a player short of cards gives all of them, winnings included
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::RemoveUpTo(nr),
+                ..
+            },
+        ) => {
+            let rest = model.len();
+            let command = Command::RemoveCards(if rest == 0 { nr } else { rest.min(nr) });
+            let msg = Msg {
+                from: sender,
+                command,
+                id,
+            };
+            let (model, cmd) = update(model, msg);
+            let response = match cmd.response {
+                Response::CardsRemoved(cards) | Response::CardsRemovedAfterReshuffle(cards)
+                    if rest < nr =>
+                {
+                    Response::CardsPartiallyRemoved(cards)
+                }
+                response => response,
+            };
+            (model, Cmd { response, ..cmd })
+        }
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::CountCards,
+                ..
+            },
+        ) => {
+            let nr_cards = model.len();
+            (
+                model,
+                Cmd {
+                    game: sender,
+                    response: Response::CardsCounted(nr_cards),
+                    id,
+                },
+            )
+        }
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::PeekTop(nr),
+                ..
+            },
+        ) => {
+            let skip = model.hand.len().saturating_sub(nr);
+            let top_cards = model.hand.iter().skip(skip).cloned().collect();
+            (
+                model,
+                Cmd {
+                    game: sender,
+                    response: Response::TopCards(top_cards),
+                    id,
+                },
+            )
+        }
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::HandSummary,
+                ..
+            },
+        ) => {
+            let summary = hand_summary(&model.cards());
+            (
+                model,
+                Cmd {
+                    game: sender,
+                    response: Response::HandSummary(summary),
+                    id,
+                },
+            )
+        }
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::HandHash,
+                ..
+            },
+        ) => {
+            let hash = model.hand_hash();
+            (
+                model,
+                Cmd {
+                    game: sender,
+                    response: Response::HandHash(hash),
+                    id,
+                },
+            )
+        }
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::Batch(commands),
+                ..
+            },
+        ) => {
+            // a batch which would fail is answered with the response of the
+            // failing command, the model is kept
+            let refused = refused(
+                &model,
+                &sender,
+                &commands,
+                &mut model.len(),
+                &mut Held::default(),
+            );
+            if let Some(response) = refused {
+                return (
+                    model,
+                    Cmd {
+                        game: sender,
+                        response: Response::Batch(vec![response]),
+                        id,
+                    },
+                );
+            }
+            let mut new_model = model;
+            let mut responses = vec![];
+            for command in commands {
+                let msg = Msg {
+                    from: sender.clone(),
+                    command,
+                    id,
+                };
+                let (next_model, cmd) = update(new_model, msg);
+                new_model = next_model;
+                responses.push(cmd.response);
+            }
+            (
+                new_model,
+                Cmd {
+                    game: sender,
+                    response: Response::Batch(responses),
+                    id,
+                },
+            )
+        }
+        (
+            model,
+            Msg {
+                from: sender,
+                command: Command::Resign,
+                ..
+            },
+        ) => {
+            // the game is told, whoever asked the player to resign
+            let game = model.game.clone().unwrap_or(sender);
+            (
+                model,
+                Cmd {
+                    game,
+                    response: Response::Resigned,
+                    id,
+                },
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod update {
+    use super::*;
+    use crate::cards::Suit;
+    use crate::cards::Value;
+
+    #[test]
+    fn add_card_to_empty_model() {
+        let model = Model::default();
+        let card = Card(Suit::Heart, Value::Ace, 0);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::AddCards(vec![card], Placement::Top),
+            id: 0,
+        };
+        let (updated_model, _cmd) = update(model, msg);
+        assert_eq!(updated_model.hand, vec![card]);
+        assert_eq!(updated_model.game, Some(vec![0]));
+    }
+
+    #[test]
+    fn add_cards_to_model() {
+        let model = Model::from(vec![
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Club, Value::Three, 0),
+            Card(Suit::Diamond, Value::Ace, 0),
+        ]);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::AddCards(
+                vec![
+                    Card(Suit::Heart, Value::Two, 0),
+                    Card(Suit::Heart, Value::Three, 0),
+                    Card(Suit::Spade, Value::Ace, 0),
+                ],
+                Placement::Top,
+            ),
+            id: 0,
+        };
+        let (updated_model, _cmd) = update(model, msg);
+        assert_eq!(
+            updated_model.hand,
+            vec![
+                Card(Suit::Club, Value::Two, 0),
+                Card(Suit::Club, Value::Three, 0),
+                Card(Suit::Diamond, Value::Ace, 0),
+                Card(Suit::Heart, Value::Two, 0),
+                Card(Suit::Heart, Value::Three, 0),
+                Card(Suit::Spade, Value::Ace, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn add_cards_to_bottom() {
+        let model = Model::from(vec![
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Club, Value::Three, 0),
+            Card(Suit::Diamond, Value::Ace, 0),
+        ]);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::AddCards(
+                vec![
+                    Card(Suit::Heart, Value::Two, 0),
+                    Card(Suit::Heart, Value::Three, 0),
+                ],
+                Placement::Bottom,
+            ),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model, msg);
+        assert_eq!(
+            updated_model.hand,
+            vec![
+                Card(Suit::Heart, Value::Two, 0),
+                Card(Suit::Heart, Value::Three, 0),
+                Card(Suit::Club, Value::Two, 0),
+                Card(Suit::Club, Value::Three, 0),
+                Card(Suit::Diamond, Value::Ace, 0),
+            ]
+        );
+        assert_eq!(cmd.response, Response::CardsAdded(2));
+    }
+
+    #[test]
+    fn won_cards_played_last() {
+        let won = Card(Suit::Spade, Value::Ace, 0);
+        for (placement, played) in [
+            (Placement::Bottom, Card(Suit::Club, Value::Two, 0)),
+            (Placement::Top, won),
+        ]
+        .iter()
+        {
+            let model = Model::from(vec![Card(Suit::Club, Value::Two, 0)]);
+            let add = Msg {
+                from: vec![0],
+                command: Command::AddCards(vec![won], *placement),
+                id: 0,
+            };
+            let (model, _cmd) = update(model, add);
+            let remove = Msg {
+                from: vec![0],
+                command: Command::RemoveCards(1),
+                id: 0,
+            };
+            let (_model, cmd) = update(model, remove);
+            assert_eq!(cmd.response, Response::CardsRemoved(vec![*played]));
+        }
+    }
+
+    #[test]
+    fn won_cards_picked_up_high_first() {
+        let model = Model {
+            pickup: Pickup::HighFirst,
+            ..Model::from(vec![Card(Suit::Club, Value::Two, 0)])
+        };
+        let msg = Msg {
+            from: vec![0],
+            command: Command::AddCards(
+                vec![
+                    Card(Suit::Heart, Value::Ace, 0),
+                    Card(Suit::Heart, Value::Three, 0),
+                ],
+                Placement::Top,
+            ),
+            id: 0,
+        };
+        let (updated_model, _cmd) = update(model, msg);
+        assert_eq!(
+            updated_model.hand,
+            vec![
+                Card(Suit::Club, Value::Two, 0),
+                Card(Suit::Heart, Value::Three, 0),
+                Card(Suit::Heart, Value::Ace, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn won_cards_kept_aside() {
+        let model = Model::from(vec![Card(Suit::Club, Value::Two, 0)]);
+        let won = vec![
+            Card(Suit::Heart, Value::Two, 0),
+            Card(Suit::Diamond, Value::Two, 0),
+        ];
+        let msg = Msg {
+            from: vec![0],
+            command: Command::AddCards(won.clone(), Placement::Winnings),
+            id: 0,
+        };
+        let (updated_model, _cmd) = update(model, msg);
+        assert_eq!(updated_model.hand, vec![Card(Suit::Club, Value::Two, 0)]);
+        assert_eq!(updated_model.winnings, won);
+        assert_eq!(updated_model.len(), 3);
+    }
+
+    #[test]
+    fn remove_cards_after_reshuffle() {
+        let top = Card(Suit::Spade, Value::Ace, 0);
+        let winnings = vec![
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Heart, Value::Two, 0),
+            Card(Suit::Diamond, Value::Two, 0),
+        ];
+        let model = Model {
+            hand: vec![top].into(),
+            winnings: winnings.clone(),
+            seed: 42,
+            ..Model::default()
+        }
+        .indexed();
+        let msg = Msg {
+            from: vec![0],
+            command: Command::RemoveCards(2),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model.clone(), msg.clone());
+        match cmd.response {
+            Response::CardsRemovedAfterReshuffle(cards) => {
+                // rest of the hand is played first
+                assert_eq!(cards.len(), 2);
+                assert_eq!(cards[1], top);
+                assert!(winnings.contains(&cards[0]));
+            }
+            response => panic!("unexpected response {:?}", response),
+        }
+        assert_eq!(updated_model.hand.len(), 2);
+        assert!(updated_model.winnings.is_empty());
+        assert_ne!(updated_model.seed, 42);
+        assert_eq!(update(model, msg).0, updated_model);
+    }
+
+    #[test]
+    fn deal_into_the_hand() {
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let two = Card(Suit::Club, Value::Two, 0);
+        let model = Model {
+            hand: vec![ace].into(),
+            ..Model::default()
+        }
+        .indexed();
+        let msg = Msg {
+            from: vec![0],
+            command: Command::Deal(vec![two], 7),
+            id: 0,
+        };
+        let (model, cmd) = update(model, msg);
+        assert_eq!(cmd.response, Response::CardsAdded(1));
+        assert_eq!(model.hand, VecDeque::from(vec![two, ace]));
+        assert_eq!((model.seed, model.game), (7, Some(vec![0])));
+    }
+
+    #[test]
+    fn remove_up_to() {
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let two = Card(Suit::Club, Value::Two, 0);
+        let model = Model {
+            hand: vec![ace].into(),
+            winnings: vec![two],
+            ..Model::default()
+        }
+        .indexed();
+        let remove_up_to = |model: Model, nr| {
+            let msg = Msg {
+                from: vec![0],
+                command: Command::RemoveUpTo(nr),
+                id: 0,
+            };
+            update(model, msg)
+        };
+        let (_, cmd) = remove_up_to(model.clone(), 1);
+        assert_eq!(cmd.response, Response::CardsRemoved(vec![ace]));
+        let (model, cmd) = remove_up_to(model, 4);
+        assert_eq!(
+            cmd.response,
+            Response::CardsPartiallyRemoved(vec![two, ace])
+        );
+        assert_eq!(model.len(), 0);
+        let (_, cmd) = remove_up_to(model, 4);
+        assert_eq!(cmd.response, Response::UnableToRemoveCards(4));
+    }
+
+    #[test]
+    fn remove_cards_without_reshuffle() {
+        let model = Model {
+            hand: vec![Card(Suit::Spade, Value::Ace, 0)].into(),
+            winnings: vec![Card(Suit::Club, Value::Two, 0)],
+            seed: 42,
+            ..Model::default()
+        }
+        .indexed();
+        let msg = Msg {
+            from: vec![0],
+            command: Command::RemoveCards(1),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model, msg);
+        assert_eq!(
+            cmd.response,
+            Response::CardsRemoved(vec![Card(Suit::Spade, Value::Ace, 0)])
+        );
+        assert_eq!(
+            updated_model.winnings,
+            vec![Card(Suit::Club, Value::Two, 0)]
+        );
+        let msg = Msg {
+            from: vec![0],
+            command: Command::RemoveCards(2),
+            id: 0,
+        };
+        let (_model, cmd) = update(updated_model, msg);
+        assert_eq!(cmd.response, Response::UnableToRemoveCards(2));
+    }
+
+    #[test]
+    fn queries_keep_the_model() {
+        let model = Model {
+            hand: vec![
+                Card(Suit::Club, Value::Two, 0),
+                Card(Suit::Club, Value::Three, 0),
+                Card(Suit::Diamond, Value::Ace, 0),
+            ]
+            .into(),
+            winnings: vec![Card(Suit::Heart, Value::Two, 0)],
+            ..Model::default()
+        }
+        .indexed();
+        let query = |command| {
+            let msg = Msg {
+                from: vec![0],
+                command,
+                id: 0,
+            };
+            let (updated_model, cmd) = update(model.clone(), msg);
+            assert_eq!(updated_model, model);
+            cmd.response
+        };
+        assert_eq!(query(Command::CountCards), Response::CardsCounted(4));
+        assert_eq!(
+            query(Command::PeekTop(2)),
+            Response::TopCards(vec![
+                Card(Suit::Club, Value::Three, 0),
+                Card(Suit::Diamond, Value::Ace, 0),
+            ])
+        );
+        match query(Command::PeekTop(5)) {
+            Response::TopCards(cards) => assert_eq!(cards.len(), 3),
+            response => panic!("unexpected response {:?}", response),
+        }
+        assert_eq!(
+            query(Command::HandHash),
+            Response::HandHash(model.hand_hash())
+        );
+        let mut moved = model.clone();
+        moved.winnings.insert(0, moved.hand.pop_front().unwrap());
+        assert_eq!(moved.cards(), model.cards());
+        assert_ne!(moved.hand_hash(), model.hand_hash());
+        match query(Command::HandSummary) {
+            Response::HandSummary(summary) => {
+                assert_eq!(
+                    summary.values,
+                    vec![(Value::Two, 2), (Value::Three, 1), (Value::Ace, 1)]
+                );
+                assert_eq!(summary.suits.len(), 3);
+            }
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn duplicated_cards_refused() {
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let model = Model::from(vec![ace]);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::AddCards(
+                vec![Card(Suit::Spade, Value::Ace, 1), ace],
+                Placement::Bottom,
+            ),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model.clone(), msg);
+        assert_eq!(updated_model, model);
+        assert_eq!(
+            cmd.response,
+            Response::Error(PlayerError::DuplicatedCards(vec![ace]))
+        );
+        let two = Card(Suit::Club, Value::Two, 0);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::AddCards(vec![two, two], Placement::Bottom),
+            id: 0,
+        };
+        let (_model, cmd) = update(model, msg);
+        assert_eq!(
+            cmd.response,
+            Response::Error(PlayerError::DuplicatedCards(vec![two]))
+        );
+    }
+
+    #[test]
+    fn removed_cards_can_come_back() {
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let model = Model::from(vec![Card(Suit::Club, Value::Two, 0), ace]);
+        let remove = Msg {
+            from: vec![0],
+            command: Command::RemoveCards(1),
+            id: 0,
+        };
+        let (model, _cmd) = update(model, remove);
+        let add = Msg {
+            from: vec![0],
+            command: Command::AddCards(vec![ace], Placement::Bottom),
+            id: 0,
+        };
+        let (model, cmd) = update(model, add.clone());
+        assert_eq!(cmd.response, Response::CardsAdded(1));
+        assert_eq!(model, model.clone().indexed());
+        let (_model, cmd) = update(model, add);
+        assert_eq!(
+            cmd.response,
+            Response::Error(PlayerError::DuplicatedCards(vec![ace]))
+        );
+    }
+
+    #[test]
+    fn no_cards_to_remove() {
+        let model = Model::from(vec![Card(Suit::Spade, Value::Ace, 0)]);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::RemoveCards(0),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model.clone(), msg);
+        assert_eq!(updated_model, model);
+        assert_eq!(cmd.response, Response::Error(PlayerError::NoCardsToRemove));
+    }
+
+    #[test]
+    fn only_dealing_game_changes_hand() {
+        let deal = Msg {
+            from: vec![1],
+            command: Command::AddCards(vec![Card(Suit::Spade, Value::Ace, 0)], Placement::Bottom),
+            id: 0,
+        };
+        let (model, _cmd) = update(Model::default(), deal);
+        assert_eq!(model.game, Some(vec![1]));
+        let remove = Msg {
+            from: vec![2],
+            command: Command::RemoveCards(1),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model.clone(), remove);
+        assert_eq!(updated_model, model);
+        assert_eq!(
+            cmd,
+            Cmd {
+                game: vec![2],
+                response: Response::Error(PlayerError::WrongGame(vec![2])),
+                id: 0,
+            }
+        );
+        let count = Msg {
+            from: vec![2],
+            command: Command::CountCards,
+            id: 0,
+        };
+        assert_eq!(update(model, count).1.response, Response::CardsCounted(1));
+    }
+
+    #[test]
+    fn id_is_echoed() {
+        let model = Model::from(vec![Card(Suit::Club, Value::Two, 0)]);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::RemoveCards(1),
+            id: 7,
+        };
+        assert_eq!(update(model.clone(), msg).1.id, 7);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::RemoveCards(0),
+            id: 8,
+        };
+        assert_eq!(update(model, msg).1.id, 8);
+    }
+
+    #[test]
+    fn resign_tells_the_game() {
+        let deal = Msg {
+            from: vec![1],
+            command: Command::AddCards(vec![Card(Suit::Spade, Value::Ace, 0)], Placement::Bottom),
+            id: 1,
+        };
+        let (model, _cmd) = update(Model::default(), deal);
+        let resign = Msg {
+            from: vec![9],
+            command: Command::Resign,
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model.clone(), resign);
+        assert_eq!(updated_model, model);
+        assert_eq!(
+            cmd,
+            Cmd {
+                game: vec![1],
+                response: Response::Resigned,
+                id: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn batch_of_commands() {
+        let model = Model::from(vec![
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Club, Value::Three, 0),
+        ]);
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::Batch(vec![
+                Command::AddCards(vec![ace], Placement::Top),
+                Command::RemoveCards(2),
+                Command::CountCards,
+            ]),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model, msg);
+        assert_eq!(
+            cmd.response,
+            Response::Batch(vec![
+                Response::CardsAdded(1),
+                Response::CardsRemoved(vec![Card(Suit::Club, Value::Three, 0), ace]),
+                Response::CardsCounted(1),
+            ])
+        );
+        assert_eq!(
+            updated_model,
+            Model {
+                game: Some(vec![0]),
+                ..Model::from(vec![Card(Suit::Club, Value::Two, 0)])
+            }
+        );
+    }
+
+    #[test]
+    fn batch_removes_cards_it_added() {
+        let two = Card(Suit::Club, Value::Two, 0);
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::Batch(vec![
+                Command::AddCards(vec![ace], Placement::Bottom),
+                Command::RemoveCards(2),
+            ]),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(Model::from(vec![two]), msg);
+        assert_eq!(
+            cmd.response,
+            Response::Batch(vec![
+                Response::CardsAdded(1),
+                Response::CardsRemoved(vec![ace, two]),
+            ])
+        );
+        assert!(updated_model.hand.is_empty());
+    }
+
+    #[test]
+    fn failed_batch_keeps_the_model() {
+        let model = Model::from(vec![Card(Suit::Club, Value::Two, 0)]);
+        let ace = Card(Suit::Spade, Value::Ace, 0);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::Batch(vec![
+                Command::AddCards(vec![ace], Placement::Top),
+                Command::RemoveCards(3),
+                Command::CountCards,
+            ]),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model.clone(), msg);
+        assert_eq!(updated_model, model);
+        assert_eq!(
+            cmd.response,
+            Response::Batch(vec![Response::UnableToRemoveCards(3)])
+        );
+        let nested = Msg {
+            from: vec![0],
+            command: Command::Batch(vec![
+                Command::RemoveCards(1),
+                Command::Batch(vec![Command::AddCards(vec![ace, ace], Placement::Top)]),
+            ]),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model.clone(), nested);
+        assert_eq!(updated_model, model);
+        assert_eq!(
+            cmd.response,
+            Response::Batch(vec![Response::Batch(vec![Response::Error(
+                PlayerError::DuplicatedCards(vec![ace])
+            )])])
+        );
+    }
+
+    #[test]
+    fn remove_cards_success() {
+        let model = Model::from(vec![
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Club, Value::Three, 0),
+            Card(Suit::Diamond, Value::Ace, 0),
+            Card(Suit::Heart, Value::Two, 0),
+            Card(Suit::Heart, Value::Three, 0),
+            Card(Suit::Spade, Value::Ace, 0),
+        ]);
+        let msg = Msg {
+            from: vec![0],
+            command: Command::RemoveCards(2),
+            id: 0,
+        };
+        let (updated_model, cmd) = update(model, msg);
+        assert_eq!(
+            (updated_model, cmd),
+            (
+                Model::from(vec![
+                    Card(Suit::Club, Value::Two, 0),
+                    Card(Suit::Club, Value::Three, 0),
+                    Card(Suit::Diamond, Value::Ace, 0),
+                    Card(Suit::Heart, Value::Two, 0),
+                ]),
+                Cmd {
+                    game: vec![0],
+                    response: Response::CardsRemoved(vec![
+                        Card(Suit::Heart, Value::Three, 0),
+                        Card(Suit::Spade, Value::Ace, 0),
+                    ]),
+                    id: 0,
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn remove_cards_failure() {
+        let model = Model::from(vec![
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Club, Value::Three, 0),
+            Card(Suit::Diamond, Value::Ace, 0),
+            Card(Suit::Heart, Value::Two, 0),
+            Card(Suit::Heart, Value::Three, 0),
+            Card(Suit::Spade, Value::Ace, 0),
+        ]);
         let msg = Msg {
             from: vec![0],
             command: Command::RemoveCards(12),
+            id: 0,
         };
         let (updated_model, cmd) = update(model, msg);
         assert_eq!(
             (updated_model, cmd),
             (
-                vec![
-                    Card(Suit::Club, Value::Two),
-                    Card(Suit::Club, Value::Three),
-                    Card(Suit::Diamond, Value::Ace),
-                    Card(Suit::Heart, Value::Two),
-                    Card(Suit::Heart, Value::Three),
-                    Card(Suit::Spade, Value::Ace),
-                ],
+                Model::from(vec![
+                    Card(Suit::Club, Value::Two, 0),
+                    Card(Suit::Club, Value::Three, 0),
+                    Card(Suit::Diamond, Value::Ace, 0),
+                    Card(Suit::Heart, Value::Two, 0),
+                    Card(Suit::Heart, Value::Three, 0),
+                    Card(Suit::Spade, Value::Ace, 0),
+                ]),
                 Cmd {
                     game: vec![0],
-                    response: Response::UnableToRemoveCards(12)
+                    response: Response::UnableToRemoveCards(12),
+                    id: 0,
                 }
             )
         );
@@ -329,19 +1820,21 @@ mod update {
 
     #[test]
     fn remove_cards_from_empty() {
-        let model = Vec::new();
+        let model = Model::default();
         let msg = Msg {
             from: vec![0],
             command: Command::RemoveCards(2),
+            id: 0,
         };
         let (updated_model, cmd) = update(model, msg);
         assert_eq!(
             (updated_model, cmd),
             (
-                vec![],
+                Model::default(),
                 Cmd {
                     game: vec![0],
-                    response: Response::UnableToRemoveCards(2)
+                    response: Response::UnableToRemoveCards(2),
+                    id: 0,
                 }
             )
         );
@@ -11,97 +11,168 @@ defmodule GameRust do
 
   # Pids are converted by term_to_binary(pid) and stored as list of bytes
   # On Rust side they are converted to binaries (Vec<u8>)
+  # Players: 2 to 6 players in seating order, every game is played at a Table
   defmodule Pids do
     @type t :: %__MODULE__{
             supervisor: [byte()],
-            player1: [byte()],
-            player2: [byte()]
+            players: [[byte()]]
           }
-    defstruct supervisor: [0], player1: [1], player2: [2]
+    defstruct supervisor: [0], players: [[1], [2]]
+  end
+
+  # Options of a game. Seed nil means random seed, the seed actually used is
+  # returned in ModelPlayers so the game can be replayed.
+  # Deck: "DeckSpec::Standard" (52 cards), "DeckSpec::Jokers" (54),
+  # "DeckSpec::Short" (36) or "DeckSpec::Piquet" (32)
+  # Decks: nr of decks shuffled together into a shoe
+  # Ranking: "Ranking::AceHigh", "Ranking::AceLow", "Ranking::SuitTiebreak" (no
+  # wars between different cards) or "Ranking::Peace" (the lowest card wins)
+  # Trump: "Trump::None", {CardTrump.Fixed, "Suit::Heart"} or "Trump::Drawn"
+  # (recorded as CardTrump.Fixed in ModelPlayers)
+  # Shuffling: "Shuffling::Uniform", {CardShuffling.Riffles, 7} or
+  # {CardShuffling.Overhand, 3}, shuffles by hand end with a cut
+  # Placement: "Placement::Bottom" (won cards go under the hand) or
+  # "Placement::Top" (won cards are played again at once) or
+  # "Placement::Winnings" (separate pile shuffled in when the hand runs out)
+  # Rules: list of CardRule.t(), the first one that applies decides
+  # Face down: CardFaceDown.t(), cards put face down in a war
+  # Last card war: a player short of cards for a war plays the last one face
+  # up instead of losing the game
+  # Turn limit: nr of battles, nil means no limit (a game may never end). At
+  # the limit the game is decided by adjudication: "Adjudication::MostCards",
+  # "Adjudication::SuddenDeath" (one more battle, a war is a draw) or
+  # "Adjudication::Draw"
+  # Cycle history: nr of turns whose hands are remembered, 0 means no cycle
+  # detection. The players are asked for hashes of their hands before every
+  # battle, one more round trip per turn
+  defmodule Config do
+    @type t :: %__MODULE__{
+            seed: non_neg_integer | nil,
+            deck: bitstring,
+            decks: pos_integer,
+            ranking: bitstring,
+            trump: CardTrump.t(),
+            shuffling: CardShuffling.t(),
+            placement: bitstring,
+            rules: [CardRule.t()],
+            face_down: CardFaceDown.t(),
+            last_card_war: boolean,
+            turn_limit: non_neg_integer | nil,
+            adjudication: bitstring,
+            cycle_history: non_neg_integer
+          }
+    defstruct seed: nil,
+              deck: "DeckSpec::Standard",
+              decks: 1,
+              ranking: "Ranking::AceHigh",
+              trump: "Trump::None",
+              shuffling: "Shuffling::Uniform",
+              placement: "Placement::Bottom",
+              rules: [],
+              face_down: {CardFaceDown.Cards, 1},
+              last_card_war: false,
+              turn_limit: nil,
+              adjudication: "Adjudication::MostCards",
+              cycle_history: 0
+  end
+
+  # State of a game from the deal on, kept by the models after ModelNone
+  # Round: id of the commands sent last, responses with another id are dropped
+  # Turns: nr of battles started, compared with the turn limit
+  # Hands: [{turn, [hash of the hand of every player]}], see cycle history
+  defmodule State do
+    @type t :: %__MODULE__{
+            round: non_neg_integer,
+            turns: non_neg_integer,
+            hands: [{non_neg_integer, [non_neg_integer]}]
+          }
+    defstruct round: 0, turns: 0, hands: []
   end
 
   defmodule MsgResponseFromPlayer do
     require Record
-    @type t :: {__MODULE__, [byte()], PlayerRust.Cmd.t()}
-    Record.defrecord(:record, __MODULE__, player: [1], response: nil)
+    @type t :: {__MODULE__, [byte()], PlayerRust.response(), non_neg_integer}
+    Record.defrecord(:record, __MODULE__, player: [1], response: nil, id: 0)
   end
 
   @type msg :: bitstring | MsgResponseFromPlayer.t()
 
   @type pile :: [Card.t()]
 
+  # Players still in the game (in seating order), fighters the game waits for
+  # (the tied players in a war), pile taken by the winner, nr of cards asked
+  # from every fighter (0 when no cards are asked) and responses of fighters
+  # so far
+  defmodule Table do
+    @type t :: %__MODULE__{
+            players: [[byte()]],
+            fighters: [[byte()]],
+            pile: GameRust.pile(),
+            cards: non_neg_integer,
+            responses: [{[byte()], PlayerRust.response()}]
+          }
+    defstruct players: [], fighters: [], pile: [], cards: 0, responses: []
+  end
+
   defmodule ModelNone do
     require Record
-    @type t :: {__MODULE__, Pids.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil)
+    @type t :: {__MODULE__, Pids.t(), Config.t()}
+    Record.defrecord(:record, __MODULE__, pids: nil, config: %Config{})
   end
 
   defmodule ModelPlayers do
     require Record
-    @type t :: {__MODULE__, Pids.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil)
+    @type t :: {__MODULE__, Pids.t(), Config.t(), State.t(), Table.t()}
+    Record.defrecord(:record, __MODULE__, pids: nil, config: nil, state: nil, table: nil)
   end
 
-  defmodule ModelPlayersWithResponse do
+  defmodule ModelBattle do
     require Record
-    @type t :: {__MODULE__, Pids.t(), [byte()], PlayerRust.Cmd.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil, player: [0], response: nil)
+    @type t :: {__MODULE__, Pids.t(), Config.t(), State.t(), Table.t()}
+    Record.defrecord(:record, __MODULE__, pids: nil, config: nil, state: nil, table: nil)
   end
 
-  defmodule ModelBattle do
+  # players were asked to count their cards or for the card of a sudden death
+  defmodule ModelAdjudication do
     require Record
-    @type t :: {__MODULE__, Pids.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil)
+    @type t :: {__MODULE__, Pids.t(), Config.t(), State.t(), Table.t()}
+    Record.defrecord(:record, __MODULE__, pids: nil, config: nil, state: nil, table: nil)
   end
 
-  defmodule ModelBattleWithResponse do
+  defmodule ModelTie do
     require Record
-    @type t :: {__MODULE__, Pids.t(), [byte()], PlayerRust.Cmd.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil, player: [1], response: nil)
+    @type t :: {__MODULE__, Pids.t()}
+    Record.defrecord(:record, __MODULE__, pids: nil)
   end
 
-  defmodule ModelBattleWonByPlayer do
+  # winner: pid (as list) of the winner, nil for a draw
+  defmodule ModelAdjudicated do
     require Record
-    @type t :: {__MODULE__, Pids.t(), [byte()]}
-    Record.defrecord(:record, __MODULE__, pids: nil, player: [1])
+    @type t :: {__MODULE__, Pids.t(), bitstring, [byte()] | nil}
+    Record.defrecord(:record, __MODULE__, pids: nil, adjudication: nil, winner: nil)
   end
 
-  defmodule ModelWar do
+  # players were asked for hashes of their hands (PlayerRust.CommandHandHash)
+  defmodule ModelHashing do
     require Record
-    @type t :: {__MODULE__, Pids.t(), GameRust.pile()}
-    Record.defrecord(:record, __MODULE__, pids: nil, pile: nil)
+    @type t :: {__MODULE__, Pids.t(), Config.t(), State.t(), Table.t()}
+    Record.defrecord(:record, __MODULE__, pids: nil, config: nil, state: nil, table: nil)
   end
 
-  defmodule ModelWarWithResponse do
+  # the hands repeat every length turns since the turn start
+  defmodule ModelCycle do
     require Record
-    @type t :: {__MODULE__, Pids.t(), GameRust.pile(), [byte()], PlayerRust.Cmd.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil, pile: nil, player: [1], resp: nil)
+    @type t :: {__MODULE__, Pids.t(), non_neg_integer, non_neg_integer}
+    Record.defrecord(:record, __MODULE__, pids: nil, length: 0, start: 0)
   end
 
-  defmodule ModelWarWonByPlayer do
+  # the last player at the table
+  defmodule ModelPlayerWon do
     require Record
     @type t :: {__MODULE__, Pids.t(), [byte()]}
     Record.defrecord(:record, __MODULE__, pids: nil, player: [1])
   end
 
-  defmodule ModelPlayer1Won do
-    require Record
-    @type t :: {__MODULE__, Pids.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil)
-  end
-
-  defmodule ModelPlayer2Won do
-    require Record
-    @type t :: {__MODULE__, Pids.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil)
-  end
-
-  defmodule ModelTie do
-    require Record
-    @type t :: {__MODULE__, Pids.t()}
-    Record.defrecord(:record, __MODULE__, pids: nil)
-  end
-
   defmodule ModelError do
     require Record
     @type t :: {__MODULE__, Pids.t(), bitstring}
@@ -111,24 +182,23 @@ defmodule GameRust do
   @type model ::
           ModelNone.t()
           | ModelPlayers.t()
-          | ModelPlayersWithResponse.t()
           | ModelBattle.t()
-          | ModelBattleWithResponse.t()
-          | ModelBattleWonByPlayer.t()
-          | ModelWar.t()
-          | ModelWarWithResponse.t()
-          | ModelWarWonByPlayer.t()
-          | ModelPlayer1Won.t()
-          | ModelPlayer2Won.t()
+          | ModelAdjudication.t()
           | ModelTie.t()
+          | ModelAdjudicated.t()
+          | ModelHashing.t()
+          | ModelCycle.t()
+          | ModelPlayerWon.t()
           | ModelError.t()
 
+  # commands for the same player are sent as one PlayerRust.CommandBatch
   defmodule SendCmd do
     @type t :: %__MODULE__{
             to: [byte()],
-            cmd: PlayerRust.Msg.t()
+            cmd: PlayerRust.command(),
+            id: non_neg_integer
           }
-    defstruct to: [1], cmd: nil
+    defstruct to: [1], cmd: nil, id: 0
   end
 
   defmodule SendCmds do
@@ -153,16 +223,16 @@ defmodule GameRust do
       {model, cmd} ->
         supervisor =
           case model do
-            {_model, %GameRust.Pids{supervisor: supervisor, player1: _, player2: _}} ->
+            {_model, %GameRust.Pids{supervisor: supervisor}} ->
               supervisor
 
-            {_model, %GameRust.Pids{supervisor: supervisor, player1: _, player2: _}, _} ->
+            {_model, %GameRust.Pids{supervisor: supervisor}, _} ->
               supervisor
 
-            {_model, %GameRust.Pids{supervisor: supervisor, player1: _, player2: _}, _, _} ->
+            {_model, %GameRust.Pids{supervisor: supervisor}, _, _} ->
               supervisor
 
-            {_model, %GameRust.Pids{supervisor: supervisor, player1: _, player2: _}, _, _, _} ->
+            {_model, %GameRust.Pids{supervisor: supervisor}, _, _, _} ->
               supervisor
           end
 
@@ -173,8 +243,8 @@ defmodule GameRust do
   @spec send_cmd(cmd) :: nil
   def send_cmd(cmd) do
     case cmd do
-      %GameRust.SendCmd{cmd: cmd, to: pid} ->
-        msg = %PlayerRust.Msg{from: Helper.pid_to_list(self()), command: cmd}
+      %GameRust.SendCmd{cmd: cmd, to: pid, id: id} ->
+        msg = %PlayerRust.Msg{from: Helper.pid_to_list(self()), command: cmd, id: id}
         send(Helper.list_to_pid(pid), msg)
 
       _ ->
@@ -5,6 +5,15 @@ defmodule Start do
   Play_rust and play_elixir functions construct game (arbiter) and players
   processes and then send message \"Msg::StartGame\" to Game process.
 
+  Play takes optional game options, e.g.
+  `Start.play(%GameRust.Config{seed: 42, deck: "DeckSpec::Piquet"})`. The seed
+  of every game is shown in ModelPlayers in the replay file, playing again with
+  the same options replays the game. A game may never end, limit it with e.g.
+  `Start.play(%GameRust.Config{turn_limit: 1000})` or end it when the hands
+  repeat with `Start.play(%GameRust.Config{cycle_history: 100})`. Up to 6
+  players sit at the table, e.g. `Start.play(%GameRust.Config{}, 4)`, the last
+  one left wins.
+
   """
 
   require GameRust.ModelNone
@@ -12,17 +21,27 @@ defmodule Start do
   @spec process_rust(pid) :: nil
   defp process_rust(file) do
     receive do
-      {{GameRust.ModelPlayer1Won, _}, _, _} ->
+      {{GameRust.ModelTie, _}, _, _} ->
         IO.puts(file, "")
-        IO.puts(file, "The End: Player 1 Won!")
+        IO.puts(file, "The End: Tie!")
 
-      {{GameRust.ModelPlayer2Won, _}, _, _} ->
+      {{GameRust.ModelAdjudicated, _, adjudication, nil}, _, _} ->
         IO.puts(file, "")
-        IO.puts(file, "The End: Player 2 Won!")
+        IO.puts(file, "The End: Draw by #{adjudication}!")
 
-      {{GameRust.ModelTie, _}, _, _} ->
+      {{GameRust.ModelAdjudicated, pids, adjudication, winner}, _, _} ->
+        nr = Enum.find_index(pids.players, &(&1 == winner)) + 1
         IO.puts(file, "")
-        IO.puts(file, "The End: Tie!")
+        IO.puts(file, "The End: Player #{nr} Won by #{adjudication}!")
+
+      {{GameRust.ModelPlayerWon, pids, player}, _, _} ->
+        nr = Enum.find_index(pids.players, &(&1 == player)) + 1
+        IO.puts(file, "")
+        IO.puts(file, "The End: Player #{nr} Won!")
+
+      {{GameRust.ModelCycle, _, length, start}, _, _} ->
+        IO.puts(file, "")
+        IO.puts(file, "The End: Cycle of #{length} turns since turn #{start}!")
 
       {{GameRust.ModelError, err}, _, _} ->
         IO.puts(file, "")
@@ -41,30 +60,26 @@ defmodule Start do
     end
   end
 
-  @spec play() :: nil
-  def play() do
+  @spec play(GameRust.Config.t(), pos_integer) :: nil
+  def play(config \\ %GameRust.Config{}, nr_of_players \\ 2) do
     case File.open("replay.txt", [:write]) do
       {:ok, file} ->
         IO.puts(file, "NIF Serde Rustler Version")
         IO.puts(file, "")
-        player1 = spawn(PlayerRust, :process, [])
-        player2 = spawn(PlayerRust, :process, [])
-
+        players = Enum.map(1..max(nr_of_players, 2), fn _ -> spawn(PlayerRust, :process, []) end)
         pids = %GameRust.Pids{
           supervisor: Helper.pid_to_list(self()),
-          player1: Helper.pid_to_list(player1),
-          player2: Helper.pid_to_list(player2)
+          players: Enum.map(players, &Helper.pid_to_list/1)
         }
 
-        game_model = GameRust.ModelNone.record(pids: pids)
+        game_model = GameRust.ModelNone.record(pids: pids, config: config)
         game = spawn(GameRust, :process, [game_model])
 
         send(game, "Msg::StartGame")
 
         process_rust(file)
         File.close(file)
-        :erlang.exit(player1, :kill)
-        :erlang.exit(player2, :kill)
+        Enum.each(players, &:erlang.exit(&1, :kill))
         :erlang.exit(game, :kill)
 
       {:error, _} ->
@@ -0,0 +1,293 @@
+/*
+Conservation of cards. Every card of the shoe must be in exactly one place:
+a hand of a player, the pile or a response kept at the table of the game model,
+or an AddCards or Deal command on its way to a player. Cards are told apart by their deck tag.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cards::{Card, Shoe};
+use crate::game::{Cmd, Model, Msg, SendCmd};
+use crate::player::Command::{self, AddCards};
+use crate::player::Response::{
+    self, CardsPartiallyRemoved, CardsRemoved, CardsRemovedAfterReshuffle,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditError {
+    Duplicated(Card),
+    Lost(Card),
+    NoShoe, // game is over, config of the game is gone
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditError::Duplicated(card) => {
+                write!(f, "card duplicated: {} (deck {})", card, card.2)
+            }
+            AuditError::Lost(card) => write!(f, "card lost: {} (deck {})", card, card.2),
+            AuditError::NoShoe => write!(f, "model without config of the game"),
+        }
+    }
+}
+
+// nr of copies of every card, cards are keyed by index and deck tag
+type Count = BTreeMap<(u8, u8), i64>;
+
+fn count(count: &mut Count, cards: &[Card], copies: i64) {
+    for card in cards {
+        *count.entry((card.index(), card.2)).or_insert(0) += copies;
+    }
+}
+
+// first card with wrong nr of copies
+fn compare(expected: &Count, found: &Count) -> Result<usize, AuditError> {
+    let mut keys: Vec<&(u8, u8)> = expected.keys().chain(found.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        let expected = expected.get(key).cloned().unwrap_or(0);
+        let found = found.get(key).cloned().unwrap_or(0);
+        if expected != found {
+            let card = Card::from_index(key.0, key.1).unwrap();
+            return if found > expected {
+                Err(AuditError::Duplicated(card))
+            } else {
+                Err(AuditError::Lost(card))
+            };
+        }
+    }
+    Ok(found.values().sum::<i64>() as usize)
+}
+
+// cards kept at the table of the game model
+fn model_cards(model: &Model) -> Vec<Card> {
+    let mut cards = vec![];
+    if let Some(table) = model.table() {
+        cards.extend_from_slice(&table.pile);
+        for (_, response) in &table.responses {
+            if let CardsRemoved(removed) | CardsPartiallyRemoved(removed) = response {
+                cards.extend_from_slice(removed);
+            }
+        }
+    }
+    cards
+}
+
+// cards sent to players
+fn cmd_cards(cmd: &Cmd) -> Vec<Card> {
+    fn added_cards(cards: &mut Vec<Card>, command: &Command) {
+        match command {
+            AddCards(added, _) | Command::Deal(added, _) => cards.extend_from_slice(added),
+            Command::Batch(commands) => {
+                for command in commands {
+                    added_cards(cards, command);
+                }
+            }
+            _ => (),
+        }
+    }
+    let mut cards = vec![];
+    if let Cmd::SendCmds(cmds) = cmd {
+        for SendCmd { to: _, cmd, id: _ } in cmds {
+            added_cards(&mut cards, cmd);
+        }
+    }
+    cards
+}
+
+fn msg_cards(msg: &Msg) -> Vec<Card> {
+    fn removed_cards(response: &Response) -> Vec<Card> {
+        match response {
+            CardsRemoved(removed)
+            | CardsRemovedAfterReshuffle(removed)
+            | CardsPartiallyRemoved(removed) => removed.to_vec(),
+            Response::Batch(responses) => responses.iter().flat_map(removed_cards).collect(),
+            _ => vec![],
+        }
+    }
+    match msg {
+        Msg::ResponseFromPlayer(_, response, _) => removed_cards(response),
+        _ => vec![],
+    }
+}
+
+/*
+Check a whole game: the shoe of the game must be split between hands of the
+players, the game model and the cmd. Cards in messages not delivered yet are
+missing, so it should be called when players have answered, e.g. after update
+returned Model::Battle. Returns nr of cards audited.
+*/
+pub fn check_game(model: &Model, cmd: &Cmd, hands: &[Vec<Card>]) -> Result<usize, AuditError> {
+    let config = model.config().ok_or(AuditError::NoShoe)?;
+    let mut expected = Count::new();
+    count(
+        &mut expected,
+        &Shoe::new(config.deck, config.decks).cards,
+        1,
+    );
+    let mut found = Count::new();
+    for hand in hands {
+        count(&mut found, hand, 1);
+    }
+    count(&mut found, &model_cards(model), 1);
+    count(&mut found, &cmd_cards(cmd), 1);
+    compare(&expected, &found)
+}
+
+/*
+Check one step of the game update: cards kept by the model and received with
+the msg must be kept by the new model or sent with the cmd. The deal brings the
+whole shoe, when the game is over cards are not tracked any more.
+*/
+pub fn check_update(
+    model: &Model,
+    msg: &Msg,
+    new_model: &Model,
+    cmd: &Cmd,
+) -> Result<usize, AuditError> {
+    let mut before = Count::new();
+    match (model, msg) {
+        (Model::None(_, _), Msg::StartGame) => match new_model.config() {
+            Some(config) => count(&mut before, &Shoe::new(config.deck, config.decks).cards, 1),
+            None => return Ok(0),
+        },
+        // stale responses are dropped by the game
+        (model, msg) if model.is_stale(msg) => count(&mut before, &model_cards(model), 1),
+        (model, msg) => {
+            count(&mut before, &model_cards(model), 1);
+            count(&mut before, &msg_cards(msg), 1);
+        }
+    }
+    if new_model.config().is_none() {
+        return Ok(0);
+    }
+    let mut after = Count::new();
+    count(&mut after, &model_cards(new_model), 1);
+    count(&mut after, &cmd_cards(cmd), 1);
+    compare(&before, &after)
+}
+
+#[cfg(test)]
+mod check_game {
+    use super::*;
+    use crate::cards::{DeckSpec, Suit, Value};
+    use crate::game::{pids, seated, Config, State, Table};
+
+    fn config() -> Config {
+        Config {
+            deck: DeckSpec::Piquet,
+            decks: 2,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn conserved_after_war() {
+        let mut hand = Shoe::new(DeckSpec::Piquet, 2).cards;
+        let pile = hand.split_off(60);
+        let table = Table {
+            pile,
+            ..seated(&pids())
+        };
+        let model = Model::Battle(pids(), config(), State::default(), table);
+        assert_eq!(check_game(&model, &Cmd::None, &[hand, vec![]]), Ok(64));
+    }
+
+    #[test]
+    fn copy_with_wrong_deck_tag() {
+        let mut hand = Shoe::new(DeckSpec::Piquet, 2).cards;
+        let card = hand.pop().unwrap();
+        assert_eq!(card, Card(Suit::Spade, Value::Ace, 1));
+        hand.push(Card(card.0, card.1, 0));
+        let model = Model::Battle(pids(), config(), State::default(), seated(&pids()));
+        assert_eq!(
+            check_game(&model, &Cmd::None, &[hand]),
+            Err(AuditError::Duplicated(Card(Suit::Spade, Value::Ace, 0)))
+        );
+    }
+
+    #[test]
+    fn lost_card() {
+        let mut hand = Shoe::new(DeckSpec::Piquet, 2).cards;
+        hand.remove(1);
+        let model = Model::Battle(pids(), config(), State::default(), seated(&pids()));
+        let error = check_game(&model, &Cmd::None, &[hand]).unwrap_err();
+        assert_eq!(error, AuditError::Lost(Card(Suit::Club, Value::Eight, 0)));
+        assert_eq!(error.to_string(), "card lost: 8C (deck 0)");
+    }
+
+    #[test]
+    fn game_over() {
+        let model = Model::Tie(pids());
+        assert_eq!(check_game(&model, &Cmd::None, &[]), Err(AuditError::NoShoe));
+    }
+}
+
+#[cfg(test)]
+mod check_update {
+    use super::*;
+    use crate::cards::{Suit, Value};
+    use crate::game::{pids, seated, update, Config, State, Table};
+    use crate::player::Command::RemoveCards;
+    use crate::player::Placement;
+
+    #[test]
+    fn deal_brings_whole_shoe() {
+        let model = Model::None(pids(), Config::default());
+        let (new_model, cmd) = update(model.clone(), Msg::StartGame);
+        assert_eq!(
+            check_update(&model, &Msg::StartGame, &new_model, &cmd),
+            Ok(52)
+        );
+    }
+
+    #[test]
+    fn war_keeps_all_cards() {
+        let two = Card(Suit::Club, Value::Two, 0);
+        let table = Table {
+            cards: 1,
+            responses: vec![(vec![1], CardsRemoved(vec![two]))],
+            ..seated(&pids())
+        };
+        let model = Model::Battle(pids(), Config::default(), State::default(), table);
+        let msg = Msg::ResponseFromPlayer(
+            vec![2],
+            CardsRemoved(vec![Card(Suit::Heart, Value::Two, 0)]),
+            0,
+        );
+        let (new_model, cmd) = update(model.clone(), msg.clone());
+        assert_eq!(check_update(&model, &msg, &new_model, &cmd), Ok(2));
+    }
+
+    #[test]
+    fn duplicated_card() {
+        let two = Card(Suit::Club, Value::Two, 0);
+        let table = Table {
+            pile: vec![two],
+            ..seated(&pids())
+        };
+        let model = Model::Battle(pids(), Config::default(), State::default(), table);
+        let new_model = model.clone();
+        let cmd = Cmd::SendCmds(vec![SendCmd {
+            to: vec![1],
+            cmd: AddCards(vec![two], Placement::Bottom),
+            id: 0,
+        }]);
+        assert_eq!(
+            check_update(&model, &Msg::StartGame, &new_model, &cmd),
+            Err(AuditError::Duplicated(two))
+        );
+        let cmd = Cmd::SendCmds(vec![SendCmd {
+            to: vec![1],
+            cmd: RemoveCards(1),
+            id: 0,
+        }]);
+        assert_eq!(
+            check_update(&model, &Msg::StartGame, &new_model, &cmd),
+            Ok(1)
+        );
+    }
+}
@@ -4,14 +4,112 @@ Comment in GameElixir.ex can be helpful to understand logic of this module.
 
 use serde::{Deserialize, Serialize};
 
+use crate::cards::{DeckSpec, FaceDown, Ranking, RankingPolicy, Rule, Shuffling, Trump};
+use crate::player::Placement;
 use crate::player::Response as PlayerResp;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename = "Elixir.GameRust.Pids")]
 pub struct Pids {
     pub supervisor: Vec<u8>,
-    pub player1: Vec<u8>,
-    pub player2: Vec<u8>,
+    pub players: Vec<Vec<u8>>, // in seating order, 2 to 6 players
+}
+
+/*
+Options of a game chosen in Model::None. After the deal Model::Players keeps
+them with every random choice filled in, so the game can be replayed.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "Elixir.GameRust.Config")]
+pub struct Config {
+    pub seed: Option<u64>, // None - random seed
+    pub deck: DeckSpec,
+    pub decks: u8, // nr of decks in the shoe
+    pub ranking: Ranking,
+    pub trump: Trump,
+    pub shuffling: Shuffling,
+    pub placement: Placement,       // where players put won cards
+    pub rules: Vec<Rule>,           // special rules, first one that applies decides
+    pub face_down: FaceDown,        // cards put face down in a war
+    pub last_card_war: bool,        // a player short of cards for a war plays the rest
+    pub turn_limit: Option<u64>,    // nr of battles, None - no limit
+    pub adjudication: Adjudication, // how a game is ended at the turn limit
+    // cycle detection asks the players for hashes of their hands before every
+    // battle, one more round trip per turn
+    pub cycle_history: usize, // nr of turns remembered, 0 - no cycle detection
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            seed: None,
+            deck: DeckSpec::default(),
+            decks: 1,
+            ranking: Ranking::default(),
+            trump: Trump::default(),
+            shuffling: Shuffling::default(),
+            placement: Placement::default(),
+            rules: vec![],
+            face_down: FaceDown::default(),
+            last_card_war: false,
+            turn_limit: None,
+            adjudication: Adjudication::default(),
+            cycle_history: 0,
+        }
+    }
+}
+
+impl Config {
+    // nr of cards dealt to the player at the seat, the first players get one
+    // card more when the shoe doesn't split evenly
+    pub fn hand_size(&self, players: usize, seat: usize) -> usize {
+        let shoe = self.deck.size() * self.decks as usize;
+        shoe / players + usize::from(seat < shoe % players)
+    }
+
+    // f gets the ranking of the cards of a battle, a fixed trump beats the rest
+    pub fn with_policy<T>(&self, f: impl FnOnce(&dyn RankingPolicy) -> T) -> T {
+        use crate::cards::WithTrump;
+
+        match self.trump {
+            Trump::Fixed(suit) => f(&WithTrump {
+                suit,
+                policy: self.ranking.policy(),
+            }),
+            _ => f(self.ranking.policy()),
+        }
+    }
+}
+
+// What changes while the game is played, from the deal on
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename = "Elixir.GameRust.State")]
+pub struct State {
+    pub round: u64,                  // id of commands sent last, responses must echo it
+    pub turns: u64,                  // nr of battles started
+    pub hands: Vec<(u64, Vec<u64>)>, // turn and hashes of the hands of the players
+}
+
+impl State {
+    // commands are sent with a new id
+    pub fn next_round(self) -> State {
+        State {
+            round: self.round + 1,
+            ..self
+        }
+    }
+}
+
+// Rule deciding a game which reached the turn limit
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum Adjudication {
+    #[default]
+    #[serde(rename = "Adjudication::MostCards")]
+    MostCards, // players are asked to count their cards
+    #[serde(rename = "Adjudication::SuddenDeath")]
+    SuddenDeath, // one more battle, a war is a draw
+    #[serde(rename = "Adjudication::Draw")]
+    Draw,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -19,39 +117,48 @@ pub enum Msg {
     #[serde(rename = "Msg::StartGame")]
     StartGame,
     #[serde(rename = "Elixir.GameRust.MsgResponseFromPlayer")]
-    ResponseFromPlayer(Vec<u8>, PlayerResp), // player_id, response
+    ResponseFromPlayer(Vec<u8>, PlayerResp, u64), // player_id, response, id
 }
 
 use crate::cards::Card;
 
 type Pile = Vec<Card>; // pile of cards
 
+/*
+Players of the game between the responses. Players who run out of cards or
+resign leave the table, the rest continue.
+*/
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename = "Elixir.GameRust.Table")]
+pub struct Table {
+    pub players: Vec<Vec<u8>>,  // players still in the game, in seating order
+    pub fighters: Vec<Vec<u8>>, // players the game waits for, the tied ones in a war
+    #[serde(with = "crate::cards::wire")]
+    pub pile: Pile, // cards played, taken by the winner
+    pub cards: usize,           // nr of cards asked from every fighter, 0 - no cards
+    pub responses: Vec<(Vec<u8>, PlayerResp)>, // responses of fighters so far
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Model {
     #[serde(rename = "Elixir.GameRust.ModelNone")]
-    None(Pids),
+    None(Pids, Config),
     #[serde(rename = "Elixir.GameRust.ModelPlayers")]
-    Players(Pids),
-    #[serde(rename = "Elixir.GameRust.ModelPlayersWithResponse")]
-    PlayersWithResponse(Pids, Vec<u8>, PlayerResp),
+    Players(Pids, Config, State, Table), // the cards were dealt
     #[serde(rename = "Elixir.GameRust.ModelBattle")]
-    Battle(Pids),
-    #[serde(rename = "Elixir.GameRust.ModelBattleWithResponse")]
-    BattleWithResponse(Pids, Vec<u8>, PlayerResp),
-    #[serde(rename = "Elixir.GameRust.ModelBattleWonByPlayer")]
-    BattleWonByPlayer(Pids, Vec<u8>),
-    #[serde(rename = "Elixir.GameRust.ModelWar")]
-    War(Pids, Pile),
-    #[serde(rename = "Elixir.GameRust.ModelWarWithResponse")]
-    WarWithResponse(Pids, Pile, Vec<u8>, PlayerResp),
-    #[serde(rename = "Elixir.GameRust.ModelWarWonByPlayer")]
-    WarWonByPlayer(Pids, Vec<u8>),
-    #[serde(rename = "Elixir.GameRust.ModelPlayer1Won")]
-    Player1Won(Pids),
-    #[serde(rename = "Elixir.GameRust.ModelPlayer2Won")]
-    Player2Won(Pids),
+    Battle(Pids, Config, State, Table), // a war when the fighters were tied
     #[serde(rename = "Elixir.GameRust.ModelTie")]
-    Tie(Pids),
+    Tie(Pids), // the last players ran out of cards together
+    #[serde(rename = "Elixir.GameRust.ModelAdjudication")]
+    Adjudication(Pids, Config, State, Table),
+    #[serde(rename = "Elixir.GameRust.ModelAdjudicated")]
+    Adjudicated(Pids, Adjudication, Option<Vec<u8>>), // winner, None - draw
+    #[serde(rename = "Elixir.GameRust.ModelHashing")]
+    Hashing(Pids, Config, State, Table),
+    #[serde(rename = "Elixir.GameRust.ModelCycle")]
+    Cycle(Pids, u64, u64), // length in turns, turn where the cycle started
+    #[serde(rename = "Elixir.GameRust.ModelPlayerWon")]
+    PlayerWon(Pids, Vec<u8>), // the last player at the table
     #[serde(rename = "Elixir.GameRust.ModelError")]
     Error(Pids, String),
 }
@@ -63,6 +170,7 @@ use crate::player::Command as PlayerCmd;
 pub struct SendCmd {
     pub to: Vec<u8>,
     pub cmd: PlayerCmd,
+    pub id: u64, // round of the game, see Config
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -74,831 +182,1565 @@ pub enum Cmd {
     SendCmds(Vec<SendCmd>),
 }
 
-fn cards_to_send(
-    player1_cards: &Vec<Card>,
-    player2_cards: &Vec<Card>,
-    // player1_cards: &[Card],
-    // player2_cards: &[Card],
-    pile: Option<&Vec<Card>>,
-) -> Vec<Card> {
-    let mut cards_to_send = Vec::new();
-    match pile {
-        None => {
-            cards_to_send.append(&mut player1_cards.clone());
-            cards_to_send.append(&mut player2_cards.clone());
-        }
-        Some(pile) => {
-            cards_to_send.append(&mut pile.clone());
-            cards_to_send.append(&mut player1_cards.clone());
-            cards_to_send.append(&mut player2_cards.clone());
+// The command for the cards of a war, with the last card rule a player short
+// of cards gives the rest of them
+fn war_cards(config: &Config, nr: usize) -> PlayerCmd {
+    if config.last_card_war {
+        PlayerCmd::RemoveUpTo(nr)
+    } else {
+        PlayerCmd::RemoveCards(nr)
+    }
+}
+
+// The winner picks up the won cards with its first command of the next turn,
+// update sends both in one batch. When the game is over the cards are not sent.
+fn picked_up(
+    next: (Model, Cmd),
+    winner: Vec<u8>,
+    cards: Vec<Card>,
+    placement: Placement,
+) -> (Model, Cmd) {
+    use crate::player::Command::AddCards;
+
+    match next {
+        (model, Cmd::SendCmds(mut cmds)) => {
+            let id = model.state().map_or(0, |state| state.round);
+            let add_cards = SendCmd {
+                to: winner,
+                cmd: AddCards(cards, placement),
+                id,
+            };
+            cmds.insert(0, add_cards);
+            (model, Cmd::SendCmds(cmds))
         }
-    };
-    cards_to_send
+        next => next,
+    }
+}
+
+// Commands for the same player go in one batch, in the order they were made.
+// Players are sent their commands in the order of their first command.
+// Commands made in one update share the id of the round.
+fn coalesce(cmds: Vec<SendCmd>) -> Vec<SendCmd> {
+    use crate::player::Command::Batch;
+
+    let mut batches: Vec<(Vec<u8>, Vec<PlayerCmd>, u64)> = vec![];
+    for SendCmd { to, cmd, id } in cmds {
+        match batches.iter_mut().find(|(pid, _, _)| *pid == to) {
+            Some((_, commands, _)) => commands.push(cmd),
+            None => batches.push((to, vec![cmd], id)),
+        }
+    }
+    batches
+        .into_iter()
+        .map(|(to, mut commands, id)| SendCmd {
+            cmd: match commands.len() {
+                1 => commands.remove(0),
+                _ => Batch(commands),
+            },
+            to,
+            id,
+        })
+        .collect()
 }
 
 #[cfg(test)]
-mod cards_to_send {
+mod coalesce {
     use super::*;
-
-    use crate::cards::Suit;
-    use crate::cards::Value;
+    use crate::player::Command::*;
 
     #[test]
-    fn without_pile() {
-        let player1_cards = vec![Card(Suit::Club, Value::Two)];
-        let player2_cards = vec![
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Heart, Value::Ace),
+    fn batch_per_player() {
+        let cmds = vec![
+            SendCmd {
+                to: vec![1],
+                cmd: AddCards(vec![], Placement::Bottom),
+                id: 0,
+            },
+            SendCmd {
+                to: vec![2],
+                cmd: RemoveCards(1),
+                id: 0,
+            },
+            SendCmd {
+                to: vec![1],
+                cmd: RemoveCards(1),
+                id: 0,
+            },
         ];
-        let cards_to_send = cards_to_send(&player1_cards, &player2_cards, None);
         assert_eq!(
-            cards_to_send,
+            coalesce(cmds),
             vec![
-                Card(Suit::Club, Value::Two),
-                Card(Suit::Club, Value::Three),
-                Card(Suit::Heart, Value::Ace),
+                SendCmd {
+                    to: vec![1],
+                    cmd: Batch(vec![AddCards(vec![], Placement::Bottom), RemoveCards(1)]),
+                    id: 0,
+                },
+                SendCmd {
+                    to: vec![2],
+                    cmd: RemoveCards(1),
+                    id: 0,
+                },
             ]
         );
     }
 
     #[test]
-    fn with_pile() {
-        let player1_cards = vec![Card(Suit::Club, Value::Two)];
-        let player2_cards = vec![
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Heart, Value::Ace),
-        ];
-        let pile = vec![
-            Card(Suit::Diamond, Value::King),
-            Card(Suit::Heart, Value::Ten),
+    fn single_commands_unchanged() {
+        let cmds = vec![
+            SendCmd {
+                to: vec![1],
+                cmd: RemoveCards(1),
+                id: 0,
+            },
+            SendCmd {
+                to: vec![2],
+                cmd: RemoveCards(1),
+                id: 0,
+            },
         ];
-        let cards_to_send = cards_to_send(&player1_cards, &player2_cards, Some(&pile));
+        assert_eq!(coalesce(cmds.clone()), cmds);
+        assert_eq!(coalesce(vec![]), vec![]);
+    }
+}
+
+impl Cmd {
+    pub fn coalesced(self) -> Cmd {
+        match self {
+            Cmd::SendCmds(cmds) => Cmd::SendCmds(coalesce(cmds)),
+            cmd => cmd,
+        }
+    }
+}
+
+impl Model {
+    pub fn pids(&self) -> &Pids {
+        match self {
+            Model::None(pids, _)
+            | Model::Players(pids, _, _, _)
+            | Model::Battle(pids, _, _, _)
+            | Model::Tie(pids)
+            | Model::Adjudication(pids, _, _, _)
+            | Model::Adjudicated(pids, _, _)
+            | Model::Hashing(pids, _, _, _)
+            | Model::Cycle(pids, _, _)
+            | Model::PlayerWon(pids, _)
+            | Model::Error(pids, _) => pids,
+        }
+    }
+
+    // None when the game is over
+    pub fn config(&self) -> Option<&Config> {
+        match self {
+            Model::None(_, config)
+            | Model::Players(_, config, _, _)
+            | Model::Battle(_, config, _, _)
+            | Model::Adjudication(_, config, _, _)
+            | Model::Hashing(_, config, _, _) => Some(config),
+            Model::Tie(_)
+            | Model::Adjudicated(_, _, _)
+            | Model::Cycle(_, _, _)
+            | Model::PlayerWon(_, _)
+            | Model::Error(_, _) => None,
+        }
+    }
+
+    // None before the deal and when the game is over
+    pub fn state(&self) -> Option<&State> {
+        self.phase().map(|(state, _)| state)
+    }
+
+    // None before the deal and when the game is over
+    pub fn table(&self) -> Option<&Table> {
+        self.phase().map(|(_, table)| table)
+    }
+
+    fn phase(&self) -> Option<(&State, &Table)> {
+        match self {
+            Model::Players(_, _, state, table)
+            | Model::Battle(_, _, state, table)
+            | Model::Adjudication(_, _, state, table)
+            | Model::Hashing(_, _, state, table) => Some((state, table)),
+            Model::None(_, _)
+            | Model::Tie(_)
+            | Model::Adjudicated(_, _, _)
+            | Model::Cycle(_, _, _)
+            | Model::PlayerWon(_, _)
+            | Model::Error(_, _) => None,
+        }
+    }
+
+    // response with an id of another round than the one the game waits for,
+    // a player may resign at any time
+    pub fn is_stale(&self, msg: &Msg) -> bool {
+        match (self.state(), msg) {
+            (_, Msg::ResponseFromPlayer(_, PlayerResp::Resigned, _)) => false,
+            (Some(state), Msg::ResponseFromPlayer(_, _, id)) => state.round != *id,
+            _ => false,
+        }
+    }
+}
+
+impl Table {
+    // every player is waited for
+    fn seated(players: Vec<Vec<u8>>) -> Table {
+        Table {
+            fighters: players.clone(),
+            players,
+            ..Table::default()
+        }
+    }
+
+    // a response of a fighter who didn't answer yet
+    fn with_response(mut self, pid: Vec<u8>, response: PlayerResp) -> Result<Table, String> {
+        let answered = self.responses.iter().any(|(from, _)| *from == pid);
+        if !self.fighters.contains(&pid) || answered {
+            return Err(format!(
+                "unexpected response from {:?}: {:?}",
+                pid, response
+            ));
+        }
+        self.responses.push((pid, response));
+        Ok(self)
+    }
+
+    fn answered(&self) -> bool {
+        self.responses.len() == self.fighters.len()
+    }
+}
+
+// Players 1 to nr of the test games, 0 supervises them
+#[cfg(test)]
+pub fn pids_of(nr: u8) -> Pids {
+    Pids {
+        supervisor: vec![0],
+        players: (1..=nr).map(|player| vec![player]).collect(),
+    }
+}
+
+// Players 1 and 2
+#[cfg(test)]
+pub fn pids() -> Pids {
+    pids_of(2)
+}
+
+// Table of a test game before a battle
+#[cfg(test)]
+pub fn seated(pids: &Pids) -> Table {
+    Table::seated(pids.players.clone())
+}
+
+// The fighters are sent the cmd in a new round, the model of the phase waits
+// for their responses
+fn ask(
+    (pids, config, state, table): (Pids, Config, State, Table),
+    fighters: Vec<Vec<u8>>,
+    cmd: PlayerCmd,
+    phase: fn(Pids, Config, State, Table) -> Model,
+) -> (Model, Cmd) {
+    use crate::player::Command::{RemoveCards, RemoveUpTo};
+
+    let state = state.next_round();
+    let cmds = fighters
+        .iter()
+        .map(|fighter| SendCmd {
+            to: fighter.clone(),
+            cmd: cmd.clone(),
+            id: state.round,
+        })
+        .collect();
+    let cards = match cmd {
+        RemoveCards(nr) | RemoveUpTo(nr) => nr,
+        _ => 0,
+    };
+    let table = Table {
+        fighters,
+        cards,
+        responses: vec![],
+        ..table
+    };
+    (phase(pids, config, state, table), Cmd::SendCmds(cmds))
+}
+
+// Before a battle the players are asked for hashes of their hands, when
+// cycle detection is on
+fn next_turn(pids: Pids, config: Config, state: State, table: Table) -> (Model, Cmd) {
+    use crate::player::Command::HandHash;
+
+    if config.cycle_history == 0 {
+        return start_battle(pids, config, state, table);
+    }
+    let players = table.players.clone();
+    ask(
+        (pids, config, state, table),
+        players,
+        HandHash,
+        Model::Hashing,
+    )
+}
+
+// The game is in a cycle when the hashes of the hands of the players at the
+// table were the same in one of the turns remembered. The hashes are kept
+// apart, so a cycle needs every hand to match. Otherwise they are remembered,
+// the oldest are forgotten.
+fn judge_hashes(pids: Pids, config: Config, state: State, table: Table) -> (Model, Cmd) {
+    use crate::player::Response::HandHash;
+
+    let mut hashes = vec![];
+    // in seating order, a player who left changes the nr of hashes
+    for player in &table.players {
+        match table.responses.iter().find(|(pid, _)| pid == player) {
+            Some((_, HandHash(hash))) => hashes.push(*hash),
+            response => {
+                let error = format!("Model::Hashing received wrong response: {:?}", response);
+                return (Model::Error(pids, error), Cmd::None);
+            }
+        }
+    }
+    if let Some(&(turn, _)) = state.hands.iter().find(|(_, seen)| *seen == hashes) {
+        return (Model::Cycle(pids, state.turns - turn, turn), Cmd::None);
+    }
+    let mut state = state;
+    state.hands.push((state.turns, hashes));
+    let forgotten = state.hands.len().saturating_sub(config.cycle_history);
+    state.hands.drain(..forgotten);
+    start_battle(pids, config, state, table)
+}
+
+#[cfg(test)]
+mod cycle {
+    use super::*;
+    use crate::player::Command::{HandHash, RemoveCards};
+
+    // turns with the hashes of the hands of players 1, 2, ... until the game
+    // stops battling
+    fn play(cycle_history: usize, hashes: &[&[u64]]) -> Model {
+        let pids = pids_of(hashes[0].len() as u8);
+        let config = Config {
+            cycle_history,
+            ..Config::default()
+        };
+        let mut model = Model::Battle(pids.clone(), config, State::default(), seated(&pids));
+        for hands in hashes {
+            let (config, state, table) = match model {
+                Model::Battle(_, config, state, table) => (config, state, table),
+                model => return model,
+            };
+            let (hashing, _cmd) = next_turn(pids.clone(), config, state, table);
+            let id = hashing.state().unwrap().round;
+            model = hashing;
+            for (player, &hash) in hands.iter().enumerate().rev() {
+                let player = vec![player as u8 + 1];
+                let msg = Msg::ResponseFromPlayer(player, PlayerResp::HandHash(hash), id);
+                model = update(model, msg).0;
+            }
+        }
+        model
+    }
+
+    #[test]
+    fn off_by_default() {
+        let (model, cmd) = next_turn(pids(), Config::default(), State::default(), seated(&pids()));
+        match (model, cmd) {
+            (Model::Battle(_, _, _, _), Cmd::SendCmds(cmds)) => {
+                assert_eq!(cmds[0].cmd, RemoveCards(1))
+            }
+            (model, cmd) => panic!("unexpected model {:?}, cmd {:?}", model, cmd),
+        }
+    }
+
+    #[test]
+    fn hands_are_hashed() {
+        let config = Config {
+            cycle_history: 2,
+            ..Config::default()
+        };
+        let (model, cmd) = next_turn(pids(), config.clone(), State::default(), seated(&pids()));
+        let state = State::default().next_round();
         assert_eq!(
-            cards_to_send,
-            vec![
-                Card(Suit::Diamond, Value::King),
-                Card(Suit::Heart, Value::Ten),
-                Card(Suit::Club, Value::Two),
-                Card(Suit::Club, Value::Three),
-                Card(Suit::Heart, Value::Ace),
-            ]
+            model,
+            Model::Hashing(pids(), config, state, seated(&pids()))
         );
+        assert_eq!(
+            cmd,
+            Cmd::SendCmds(vec![
+                SendCmd {
+                    to: vec![1],
+                    cmd: HandHash,
+                    id: 1,
+                },
+                SendCmd {
+                    to: vec![2],
+                    cmd: HandHash,
+                    id: 1,
+                },
+            ])
+        );
+        match play(2, &[&[1, 2], &[2, 1], &[3, 4]]) {
+            Model::Battle(_, _, state, _) => {
+                assert_eq!(state.turns, 3);
+                assert_eq!(state.hands, vec![(1, vec![2, 1]), (2, vec![3, 4])]);
+            }
+            model => panic!("unexpected model {:?}", model),
+        }
+    }
+
+    #[test]
+    fn cycle_is_found() {
+        let hashes: [&[u64]; 5] = [&[1, 2], &[5, 6], &[3, 4], &[5, 6], &[3, 4]];
+        assert_eq!(play(3, &hashes), Model::Cycle(pids(), 2, 1));
+        // turn 1 is forgotten before turn 3
+        match play(1, &hashes) {
+            Model::Battle(_, _, state, _) => assert_eq!(state.turns, 5),
+            model => panic!("unexpected model {:?}", model),
+        }
+        let table_of_three: [&[u64]; 3] = [&[1, 2, 3], &[4, 5, 6], &[1, 2, 3]];
+        assert_eq!(play(2, &table_of_three), Model::Cycle(pids_of(3), 2, 0));
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum FightResult {
-    #[serde(rename = "Elixir.GameRust.PlayerWon")]
-    PlayerWon(Vec<u8>, Vec<Card>), // player_id, cards_to_send
-    #[serde(rename = "Elixir.GameRust.Tie")]
-    Tie(Vec<Card>),
+// Every player at the table is asked for a card, unless the turn limit was
+// reached
+fn start_battle(pids: Pids, config: Config, state: State, table: Table) -> (Model, Cmd) {
+    use crate::player::Command::RemoveCards;
+
+    if config
+        .turn_limit
+        .is_some_and(|turn_limit| state.turns >= turn_limit)
+    {
+        return adjudicate(pids, config, state, table);
+    }
+    let state = State {
+        turns: state.turns + 1,
+        ..state
+    };
+    let players = table.players.clone();
+    ask(
+        (pids, config, state, table),
+        players,
+        RemoveCards(1),
+        Model::Battle,
+    )
 }
 
-fn fight_result(
-    player1: &Vec<u8>,
-    player1_cards: &Vec<Card>,
-    player2: &Vec<u8>,
-    player2_cards: &Vec<Card>,
-    pile: Option<&Vec<Card>>,
-) -> Result<FightResult, String> {
-    use crate::cards::cards_are_equal;
-    use crate::cards::first_is_less;
-
-    let correct_nr_of_cards = match (pile, player1_cards.len(), player2_cards.len()) {
-        (None, 1, 1) => true,
-        (Some(_), 2, 2) => true,
-        (_, _, _) => false,
+fn adjudicate(pids: Pids, config: Config, state: State, table: Table) -> (Model, Cmd) {
+    use crate::player::Command::{CountCards, RemoveCards};
+
+    let cmd = match config.adjudication {
+        Adjudication::MostCards => CountCards,
+        Adjudication::SuddenDeath => RemoveCards(1),
+        Adjudication::Draw => {
+            return (
+                Model::Adjudicated(pids, Adjudication::Draw, None),
+                Cmd::None,
+            )
+        }
     };
+    let players = table.players.clone();
+    ask(
+        (pids, config, state, table),
+        players,
+        cmd,
+        Model::Adjudication,
+    )
+}
 
-    if !correct_nr_of_cards {
-        Err("players must have right number of cards".to_string())
-    } else {
-        let cards_to_send = cards_to_send(player1_cards, player2_cards, pile);
-        let mut player1_cards = player1_cards.clone();
-        let mut player2_cards = player2_cards.clone();
-        match (player1_cards.pop(), player2_cards.pop()) {
-            (Some(card1), Some(card2)) => {
-                if cards_are_equal(&card1, &card2) {
-                    Ok(FightResult::Tie(cards_to_send))
-                } else if first_is_less(&card1, &card2) {
-                    Ok(FightResult::PlayerWon(player2.to_vec(), cards_to_send))
-                } else {
-                    Ok(FightResult::PlayerWon(player1.to_vec(), cards_to_send))
-                }
+// The winner has the most cards or the highest card of the sudden death, a
+// tie for the first place is a draw. Players without cards lose the sudden
+// death.
+fn judge_adjudication(pids: Pids, config: Config, _state: State, table: Table) -> (Model, Cmd) {
+    use crate::player::Response::{CardsCounted, CardsRemoved, UnableToRemoveCards};
+
+    let mut counted = vec![];
+    let mut played = vec![];
+    for (pid, response) in table.responses {
+        match (config.adjudication, response) {
+            (Adjudication::MostCards, CardsCounted(nr)) => counted.push((pid, nr)),
+            (Adjudication::SuddenDeath, CardsRemoved(ref cards)) if cards.len() == 1 => {
+                played.push((pid, cards[0]))
+            }
+            (Adjudication::SuddenDeath, UnableToRemoveCards(_)) => (),
+            (_, response) => {
+                let error = format!(
+                    "Model::Adjudication received wrong response: {:?}",
+                    response
+                );
+                return (Model::Error(pids, error), Cmd::None);
             }
-            (_, _) => Err("unable to compare cards".to_string()),
         }
     }
+    let most = counted.iter().map(|(_, nr)| *nr).max();
+    let best: Vec<Vec<u8>> = match config.adjudication {
+        Adjudication::MostCards => counted
+            .into_iter()
+            .filter(|(_, nr)| Some(*nr) == most)
+            .map(|(pid, _)| pid)
+            .collect(),
+        _ => best_cards(&config, &played),
+    };
+    let winner = match best.as_slice() {
+        [winner] => Some(winner.clone()),
+        _ => None,
+    };
+    (
+        Model::Adjudicated(pids, config.adjudication, winner),
+        Cmd::None,
+    )
 }
 
 #[cfg(test)]
-mod fight_result {
+mod adjudication {
     use super::*;
+    use crate::cards::{Suit, Value};
+    use crate::player::Command::{CountCards, RemoveCards};
+    use crate::player::Response::{CardsCounted, CardsRemoved};
 
-    use crate::cards::Suit;
-    use crate::cards::Value;
+    fn limited(adjudication: Adjudication) -> Config {
+        Config {
+            turn_limit: Some(3),
+            adjudication,
+            ..Config::default()
+        }
+    }
+
+    // the turn limit was reached
+    fn played(turns: u64) -> State {
+        State {
+            turns,
+            ..State::default()
+        }
+    }
+
+    // player 1 wins the battle of the model
+    fn fight(model: Model) -> (Model, Cmd) {
+        let king = Card(Suit::Heart, Value::King, 0);
+        let two = Card(Suit::Club, Value::Two, 0);
+        let id = model.state().map_or(0, |state| state.round);
+        let msg = Msg::ResponseFromPlayer(vec![1], CardsRemoved(vec![king]), id);
+        let (model, _cmd) = update(model, msg);
+        update(
+            model,
+            Msg::ResponseFromPlayer(vec![2], CardsRemoved(vec![two]), id),
+        )
+    }
 
-    //use crate::player::Msg::RemoveCards;
     #[test]
-    fn err_wrong_nr_of_cars_without_pile() {
-        let player1 = vec![1];
-        let player2 = vec![2];
-        let player1_c = vec![Card(Suit::Club, Value::Two), Card(Suit::Heart, Value::Ace)];
-        let player2_c = vec![
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Heart, Value::Ace),
-        ];
-        let fight_result = fight_result(&player1, &player1_c, &player2, &player2_c, None);
+    fn battles_are_counted() {
+        let config = limited(Adjudication::MostCards);
+        let (model, _cmd) = start_battle(pids(), config, played(1), seated(&pids()));
+        let (model, _cmd) = fight(model);
+        match model {
+            Model::Battle(_, _, state, _) => assert_eq!(state.turns, 3),
+            model => panic!("unexpected model {:?}", model),
+        }
+        let config = limited(Adjudication::Draw);
+        let (model, _cmd) = start_battle(pids(), config, played(2), seated(&pids()));
+        let (model, cmd) = fight(model);
         assert_eq!(
-            fight_result,
-            Err("players must have right number of cards".to_string())
+            (model, cmd),
+            (
+                Model::Adjudicated(pids(), Adjudication::Draw, None),
+                Cmd::None
+            )
         );
     }
 
     #[test]
-    fn player1_won_with_pile() {
-        let player1 = vec![1];
-        let player2 = vec![2];
-        let player1_c = vec![Card(Suit::Club, Value::Two), Card(Suit::Heart, Value::Ace)];
-        let player2_c = vec![
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Heart, Value::Ten),
-        ];
-        let pile = vec![Card(Suit::Club, Value::Three)];
-        let fight_result = fight_result(&player1, &player1_c, &player2, &player2_c, Some(&pile));
+    fn most_cards() {
+        let config = limited(Adjudication::MostCards);
+        let (model, cmd) = start_battle(pids(), config.clone(), played(3), seated(&pids()));
+        let state = played(3).next_round();
         assert_eq!(
-            fight_result,
-            Ok(FightResult::PlayerWon(
-                vec![1],
-                [
-                    Card(Suit::Club, Value::Three),
-                    Card(Suit::Club, Value::Two),
-                    Card(Suit::Heart, Value::Ace),
-                    Card(Suit::Club, Value::Three),
-                    Card(Suit::Heart, Value::Ten),
-                ]
-                .to_vec()
-            ))
+            model,
+            Model::Adjudication(pids(), config.clone(), state, seated(&pids()))
+        );
+        assert_eq!(
+            cmd,
+            Cmd::SendCmds(vec![
+                SendCmd {
+                    to: vec![1],
+                    cmd: CountCards,
+                    id: 1,
+                },
+                SendCmd {
+                    to: vec![2],
+                    cmd: CountCards,
+                    id: 1,
+                },
+            ])
+        );
+        let (model, _cmd) = update(model, Msg::ResponseFromPlayer(vec![2], CardsCounted(30), 1));
+        let (model, _cmd) = update(model, Msg::ResponseFromPlayer(vec![1], CardsCounted(22), 1));
+        assert_eq!(
+            model,
+            Model::Adjudicated(pids(), Adjudication::MostCards, Some(vec![2]))
+        );
+        // two of three players have the most cards
+        let pids = pids_of(3);
+        let (mut model, _cmd) = start_battle(pids.clone(), config, played(3), seated(&pids));
+        for &(player, nr) in [(1, 20), (2, 12), (3, 20)].iter() {
+            let msg = Msg::ResponseFromPlayer(vec![player], CardsCounted(nr), 1);
+            model = update(model, msg).0;
+        }
+        assert_eq!(
+            model,
+            Model::Adjudicated(pids, Adjudication::MostCards, None)
         );
     }
 
     #[test]
-    fn tie() {
-        let player1 = vec![1];
-        let player2 = vec![2];
-        let player1_c = vec![Card(Suit::Club, Value::Two)];
-        let player2_c = vec![Card(Suit::Heart, Value::Two)];
-        let fight_result = fight_result(&player1, &player1_c, &player2, &player2_c, None);
+    fn sudden_death() {
+        let config = limited(Adjudication::SuddenDeath);
+        let (model, cmd) = start_battle(pids(), config, played(3), seated(&pids()));
+        match cmd {
+            Cmd::SendCmds(cmds) => assert_eq!(cmds[0].cmd, RemoveCards(1)),
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
+        let king = CardsRemoved(vec![Card(Suit::Club, Value::King, 0)]);
+        let (model, _cmd) = update(model, Msg::ResponseFromPlayer(vec![1], king, 1));
+        let two = CardsRemoved(vec![Card(Suit::Heart, Value::Two, 0)]);
+        let (won, _cmd) = update(model.clone(), Msg::ResponseFromPlayer(vec![2], two, 1));
         assert_eq!(
-            fight_result,
-            Ok(FightResult::Tie(
-                [Card(Suit::Club, Value::Two), Card(Suit::Heart, Value::Two)].to_vec()
-            ))
+            won,
+            Model::Adjudicated(pids(), Adjudication::SuddenDeath, Some(vec![1]))
         );
+        let king = CardsRemoved(vec![Card(Suit::Heart, Value::King, 0)]);
+        let (war, _cmd) = update(model, Msg::ResponseFromPlayer(vec![2], king, 1));
+        assert_eq!(
+            war,
+            Model::Adjudicated(pids(), Adjudication::SuddenDeath, None)
+        );
+    }
+}
+
+/*
+A player who resigns leaves the table like a player without cards, cards they
+played in the battle stay in the pile for the winner. The game goes on with
+answered once the players left at the table answered, waiting keeps the phase.
+*/
+fn leave_table(
+    (pids, config, state, table): (Pids, Config, State, Table),
+    pid: &[u8],
+    waiting: fn(Pids, Config, State, Table) -> Model,
+    answered: fn(Pids, Config, State, Table) -> (Model, Cmd),
+) -> (Model, Cmd) {
+    if !table.players.iter().any(|player| player == pid) {
+        let error = format!("{:?} resigned from a game it does not play", pid);
+        return (Model::Error(pids, error), Cmd::None);
+    }
+    use crate::player::Response::{CardsPartiallyRemoved, CardsRemoved};
+
+    let (played, responses): (Vec<_>, Vec<_>) = table
+        .responses
+        .into_iter()
+        .partition(|(from, _)| from == pid);
+    let mut pile = table.pile;
+    for (_, response) in played {
+        if let CardsRemoved(cards) | CardsPartiallyRemoved(cards) = response {
+            pile.extend(cards);
+        }
+    }
+    let stays = |player: &Vec<u8>| player != pid;
+    let table = Table {
+        players: table.players.into_iter().filter(stays).collect(),
+        fighters: table.fighters.into_iter().filter(stays).collect(),
+        pile,
+        responses,
+        ..table
+    };
+    match table.players.as_slice() {
+        [player] => (Model::PlayerWon(pids.clone(), player.clone()), Cmd::None),
+        _ if table.answered() => answered(pids, config, state, table),
+        _ => (waiting(pids, config, state, table), Cmd::None),
+    }
+}
+
+// A response of a player in any phase of the game, the phase is judged by
+// answered once all fighters answered
+fn collect(
+    (pids, config, state, table): (Pids, Config, State, Table),
+    (pid, response): (Vec<u8>, PlayerResp),
+    waiting: fn(Pids, Config, State, Table) -> Model,
+    answered: fn(Pids, Config, State, Table) -> (Model, Cmd),
+) -> (Model, Cmd) {
+    if let PlayerResp::Resigned = response {
+        return leave_table((pids, config, state, table), &pid, waiting, answered);
+    }
+    match table.with_response(pid, response) {
+        Ok(table) if table.answered() => answered(pids, config, state, table),
+        Ok(table) => (waiting(pids, config, state, table), Cmd::None),
+        Err(error) => (Model::Error(pids, error), Cmd::None),
+    }
+}
+
+const MAX_PLAYERS: usize = 6;
+
+// Cards are dealt one by one around the table
+fn deal_players(pids: Pids, config: Config) -> (Model, Cmd) {
+    if !(2..=MAX_PLAYERS).contains(&pids.players.len()) {
+        let error = format!("Model::None: 2 to {} players", MAX_PLAYERS);
+        return (Model::Error(pids, error), Cmd::None);
     }
+    let (shoe, config) = shoe(config);
+    let state = State::default().next_round();
+    let hands = shoe.deal_round_robin(pids.players.len());
+    let send_hands = deal(&config, &state, &pids.players, hands);
+    let table = Table::seated(pids.players.clone());
+    (
+        Model::Players(pids, config, state, table),
+        Cmd::SendCmds(send_hands),
+    )
 }
 
-fn match_players_with_responses(
-    pids: Pids,
-    pid1: Vec<u8>,
-    response1: PlayerResp,
-    pid2: Vec<u8>,
-    response2: PlayerResp,
-) -> Result<((Vec<u8>, PlayerResp), (Vec<u8>, PlayerResp)), String> {
-    let Pids {
-        supervisor: _,
-        player1,
-        player2,
-    } = pids;
-    // match response with player
-    if (&player1, &player2) == (&pid1, &pid2) {
-        Ok(((player1, response1), (player2, response2)))
-    } else if (&player1, &player2) == (&pid2, &pid1) {
-        Ok(((player1, response2), (player2, response1)))
+// Every player got the cards dealt to its seat, cards of players who left are
+// not counted
+fn judge_deal(pids: Pids, config: Config, state: State, table: Table) -> (Model, Cmd) {
+    use crate::player::Response::CardsAdded;
+
+    let seats = pids.players.len();
+    let dealt = |player: &Vec<u8>| {
+        let seat = pids.players.iter().position(|pid| pid == player);
+        seat.map(|seat| config.hand_size(seats, seat))
+    };
+    let correct = table
+        .responses
+        .iter()
+        .all(|(pid, response)| match response {
+            CardsAdded(nr) => Some(*nr) == dealt(pid),
+            _ => false,
+        });
+    if correct {
+        next_turn(pids, config, state, table)
     } else {
-        let error = format!(
-            "unable to match players: {:?}, {:?} with pids: {:?}, {:?}",
-            player1, player2, pid1, pid2
-        );
-        Err(error)
+        let error = "Model::Players: players got wrong nr of cards".to_string();
+        (Model::Error(pids, error), Cmd::None)
     }
 }
 
+// Players with the highest card, more than one means a war
+fn best_cards(config: &Config, played: &[(Vec<u8>, Card)]) -> Vec<Vec<u8>> {
+    use crate::cards::compare;
+    use std::cmp::Ordering;
+
+    config.with_policy(|policy| {
+        let mut best: Vec<&(Vec<u8>, Card)> = vec![];
+        for player in played {
+            let ordering = best
+                .first()
+                .map(|(_, card)| compare(policy, &config.rules, &player.1, card));
+            match ordering {
+                Some(Ordering::Less) => (),
+                Some(Ordering::Equal) => best.push(player),
+                Some(Ordering::Greater) | None => best = vec![player],
+            }
+        }
+        best.into_iter().map(|(pid, _)| pid.clone()).collect()
+    })
+}
+
 #[cfg(test)]
-mod match_players_with_responses {
+mod best_cards {
     use super::*;
+    use crate::cards::{Suit, Value};
 
-    use crate::player::Response::CardsAdded;
-    use crate::player::Response::UnableToRemoveCards;
+    // players 1, 2, ... play the cards
+    fn best(config: Config, cards: &[Card]) -> Vec<Vec<u8>> {
+        let played: Vec<(Vec<u8>, Card)> = cards
+            .iter()
+            .enumerate()
+            .map(|(player, card)| (vec![player as u8 + 1], *card))
+            .collect();
+        best_cards(&config, &played)
+    }
 
     #[test]
-    fn matched_responses() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+    fn peace_lowest_card_wins() {
+        let config = Config {
+            ranking: Ranking::Peace,
+            ..Config::default()
         };
-        let resp1 = UnableToRemoveCards(2);
-        let resp2 = CardsAdded(3);
-        let match_players = match_players_with_responses(pids, vec![1], resp1, vec![2], resp2);
-        assert_eq!(
-            match_players,
-            Ok(((vec![1], UnableToRemoveCards(2)), (vec![2], CardsAdded(3))))
-        );
+        let cards = [
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Heart, Value::Ace, 0),
+        ];
+        assert_eq!(best(config, &cards), vec![vec![1]]);
     }
 
     #[test]
-    fn wrong_pids() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+    fn suit_tiebreak_instead_of_war() {
+        let config = Config {
+            ranking: Ranking::SuitTiebreak,
+            ..Config::default()
         };
-        let resp1 = UnableToRemoveCards(2);
-        let resp2 = CardsAdded(3);
-        let match_players = match_players_with_responses(pids, vec![1], resp1, vec![1], resp2);
-        assert_eq!(
-            match_players,
-            Err("unable to match players: [1], [2] with pids: [1], [1]".to_string())
-        );
+        let cards = [
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Heart, Value::Two, 0),
+        ];
+        assert_eq!(best(config, &cards), vec![vec![2]]);
     }
-}
 
-fn judge_players(
-    pids: Pids,
-    pid1: Vec<u8>,
-    response1: PlayerResp,
-    pid2: Vec<u8>,
-    response2: PlayerResp,
-    pile: Option<&Vec<Card>>,
-) -> (Model, Cmd) {
-    use crate::player::Command::*;
-    use crate::player::Response::CardsRemoved;
-    use crate::player::Response::UnableToRemoveCards;
+    #[test]
+    fn trump_wins() {
+        let config = Config {
+            trump: Trump::Fixed(Suit::Club),
+            ..Config::default()
+        };
+        let cards = [
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Heart, Value::Ace, 0),
+        ];
+        assert_eq!(best(config, &cards), vec![vec![1]]);
+    }
 
-    let model = match pile {
-        None => "Model::BattleWithRespose",
-        Some(_) => "Model::WarWithResponse",
-    };
+    #[test]
+    fn tie_of_copies_from_different_decks() {
+        let cards = [
+            Card(Suit::Club, Value::Two, 0),
+            Card(Suit::Club, Value::Two, 1),
+        ];
+        assert_eq!(best(Config::default(), &cards), vec![vec![1], vec![2]]);
+    }
+}
 
-    let players_with_responses =
-        match_players_with_responses(pids.clone(), pid1, response1, pid2, response2);
-    match players_with_responses {
-        Err(details) => {
-            let error = model.to_owned() + ": " + &details;
-            (Model::Error(pids, error), Cmd::None)
-        }
-        Ok(((player1, player1_response), (player2, player2_response))) => {
-            match (player1_response, player2_response) {
-                (CardsRemoved(player1_cards), CardsRemoved(player2_cards)) => {
-                    let fight_result =
-                        fight_result(&player1, &player1_cards, &player2, &player2_cards, pile);
-                    match fight_result {
-                        Ok(FightResult::PlayerWon(player, cards)) => {
-                            let cmd = vec![SendCmd {
-                                to: player.clone(),
-                                cmd: AddCards(cards),
-                            }];
-                            match pile {
-                                None => {
-                                    (Model::BattleWonByPlayer(pids, player), Cmd::SendCmds(cmd))
-                                }
-                                Some(_) => {
-                                    (Model::WarWonByPlayer(pids, player), Cmd::SendCmds(cmd))
-                                }
-                            }
-                        }
-                        Ok(FightResult::Tie(pile)) => {
-                            let remove_cards = vec![
-                                SendCmd {
-                                    to: player1,
-                                    cmd: RemoveCards(2),
-                                },
-                                SendCmd {
-                                    to: player2,
-                                    cmd: RemoveCards(2),
-                                },
-                            ];
-                            (Model::War(pids, pile), Cmd::SendCmds(remove_cards))
-                        }
-                        Err(error) => {
-                            let error = model.to_owned() + ": " + &error;
-                            (Model::Error(pids, error), Cmd::None)
-                        }
-                    }
-                }
+// Fighters without cards leave the table. The highest card takes the pile,
+// players tied with it fight a war for it. When nobody could play, the pile
+// waits for the next battle.
+fn judge_battle(pids: Pids, config: Config, state: State, table: Table) -> (Model, Cmd) {
+    use crate::player::Response::{CardsPartiallyRemoved, CardsRemoved, UnableToRemoveCards};
 
-                (CardsRemoved(_), UnableToRemoveCards(_)) => (Model::Player1Won(pids), Cmd::None),
-                (UnableToRemoveCards(_), CardsRemoved(_)) => (Model::Player2Won(pids), Cmd::None),
-                (UnableToRemoveCards(_), UnableToRemoveCards(_)) => (Model::Tie(pids), Cmd::None),
-                (resp1, resp2) => {
-                    let error = format!(
-                        "{} received wrong responses: {:?}, {:?}",
-                        model, resp1, resp2
-                    );
-                    (Model::Error(pids, error), Cmd::None)
-                }
+    let Table {
+        players,
+        fighters,
+        mut pile,
+        cards,
+        responses,
+    } = table;
+    // the last card of a player short of cards is the face-up one
+    let correct = |response: &PlayerResp| match response {
+        CardsRemoved(removed) => removed.len() == cards,
+        CardsPartiallyRemoved(removed) => {
+            config.last_card_war && !removed.is_empty() && removed.len() < cards
+        }
+        _ => true,
+    };
+    let mut played = vec![];
+    let mut out = vec![];
+    // in seating order, the pile doesn't depend on the order of responses
+    for fighter in &fighters {
+        let response = responses.iter().find(|(pid, _)| pid == fighter);
+        match response.map(|(_, response)| response) {
+            Some(response) if !correct(response) => {
+                let error = "Model::Battle: players must have right number of cards".to_string();
+                return (Model::Error(pids, error), Cmd::None);
+            }
+            Some(CardsRemoved(removed)) | Some(CardsPartiallyRemoved(removed)) => {
+                pile.extend_from_slice(removed);
+                played.push((fighter.clone(), removed[removed.len() - 1]));
+            }
+            Some(UnableToRemoveCards(_)) => out.push(fighter.clone()),
+            response => {
+                let error = format!("Model::Battle received wrong response: {:?}", response);
+                return (Model::Error(pids, error), Cmd::None);
             }
         }
     }
+    let players: Vec<Vec<u8>> = players
+        .into_iter()
+        .filter(|player| !out.contains(player))
+        .collect();
+    match players.as_slice() {
+        [] => return (Model::Tie(pids), Cmd::None),
+        [player] => return (Model::PlayerWon(pids.clone(), player.clone()), Cmd::None),
+        _ => (),
+    }
+    let table = Table::seated(players);
+    match best_cards(&config, &played).as_slice() {
+        [] => next_turn(pids, config, state, Table { pile, ..table }),
+        [winner] => {
+            let placement = config.placement;
+            let next = next_turn(pids, config, state, table);
+            picked_up(next, winner.clone(), pile, placement)
+        }
+        tied => {
+            let tied = tied.to_vec();
+            let nr = played
+                .iter()
+                .find(|(pid, _)| *pid == tied[0])
+                .map_or(1, |(_, card)| config.face_down.count(card) + 1);
+            let cmd = war_cards(&config, nr);
+            let model = (pids, config, state, Table { pile, ..table });
+            ask(model, tied, cmd, Model::Battle)
+        }
+    }
 }
 
 #[cfg(test)]
-mod judge_players {
+mod battle {
     use super::*;
+    use crate::cards::{Rule, Suit, Value};
+    use crate::player::Command::{AddCards, Batch, Deal, RemoveCards, RemoveUpTo};
+    use crate::player::Response::{
+        CardsAdded, CardsPartiallyRemoved, CardsRemoved, UnableToRemoveCards,
+    };
 
-    use crate::player::Response::CardsAdded;
-    use crate::player::Response::CardsRemoved;
-    use crate::player::Response::UnableToRemoveCards;
+    // a battle of some players of a table of four
+    fn battle(config: Config, players: &[u8], pile: Pile) -> Model {
+        let players: Vec<Vec<u8>> = players.iter().map(|&player| vec![player]).collect();
+        let table = Table {
+            pile,
+            cards: 1,
+            ..Table::seated(players)
+        };
+        Model::Battle(pids_of(4), config, State::default(), table)
+    }
+
+    fn respond(model: Model, responses: Vec<(u8, PlayerResp)>) -> (Model, Cmd) {
+        let mut result = (model, Cmd::None);
+        for (player, response) in responses {
+            let id = result.0.state().map_or(0, |state| state.round);
+            result = update(
+                result.0,
+                Msg::ResponseFromPlayer(vec![player], response, id),
+            );
+        }
+        result
+    }
+
+    fn removed(value: Value, suit: Suit) -> PlayerResp {
+        CardsRemoved(vec![Card(suit, value, 0)])
+    }
+
+    // the first command sent
+    fn first(cmd: Cmd) -> SendCmd {
+        match cmd {
+            Cmd::SendCmds(mut cmds) => cmds.remove(0),
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
+    }
 
     #[test]
-    fn wrong_pids_no_pile() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+    fn round_robin_deal() {
+        let config = Config {
+            seed: Some(7),
+            ..Config::default()
+        };
+        let (model, cmd) = update(Model::None(pids_of(4), config), Msg::StartGame);
+        let sizes: Vec<(Vec<u8>, usize)> = match cmd {
+            Cmd::SendCmds(cmds) => cmds
+                .into_iter()
+                .map(|cmd| match cmd.cmd {
+                    Deal(cards, _) => (cmd.to, cards.len()),
+                    cmd => panic!("unexpected cmd {:?}", cmd),
+                })
+                .collect(),
+            cmd => panic!("unexpected cmd {:?}", cmd),
         };
-        let resp1 = UnableToRemoveCards(2);
-        let resp2 = CardsAdded(3);
-        let judge_players = judge_players(pids.clone(), vec![1], resp1, vec![1], resp2, None);
         assert_eq!(
-            judge_players,
-            (
-                Model::Error(pids,
-                    "Model::BattleWithRespose: unable to match players: [1], [2] with pids: [1], [1]"
-                        .to_string()
-                ),
-                Cmd::None
+            sizes,
+            vec![(vec![1], 13), (vec![2], 13), (vec![3], 13), (vec![4], 13)]
+        );
+        let id = model.state().unwrap().round;
+        let mut model = model;
+        for player in 1..5 {
+            let msg = Msg::ResponseFromPlayer(vec![player], CardsAdded(13), id);
+            model = update(model, msg).0;
+        }
+        match model {
+            Model::Battle(_, _, _, table) => assert_eq!(table.fighters.len(), 4),
+            model => panic!("unexpected model {:?}", model),
+        }
+    }
+
+    #[test]
+    fn dealt_cards_are_checked() {
+        let config = Config::default();
+        assert_eq!(config.hand_size(3, 0), 18);
+        assert_eq!(config.hand_size(3, 2), 17);
+        let (model, _cmd) = update(Model::None(pids_of(3), config), Msg::StartGame);
+        let (dealt, _cmd) = respond(
+            model.clone(),
+            vec![
+                (2, CardsAdded(17)),
+                (1, CardsAdded(18)),
+                (3, CardsAdded(17)),
+            ],
+        );
+        match dealt {
+            Model::Battle(_, _, state, _) => assert_eq!(state.turns, 1),
+            model => panic!("unexpected model {:?}", model),
+        }
+        let (dealt, _cmd) = respond(
+            model,
+            vec![
+                (1, CardsAdded(17)),
+                (2, CardsAdded(18)),
+                (3, CardsAdded(17)),
+            ],
+        );
+        assert_eq!(
+            dealt,
+            Model::Error(
+                pids_of(3),
+                "Model::Players: players got wrong nr of cards".to_string()
             )
         );
     }
 
     #[test]
-    fn wrong_pids_with_pile() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+    fn two_to_six_players() {
+        for nr in [1, 7].iter() {
+            let pids = pids_of(*nr);
+            let (model, _cmd) =
+                update(Model::None(pids.clone(), Config::default()), Msg::StartGame);
+            assert_eq!(
+                model,
+                Model::Error(pids, "Model::None: 2 to 6 players".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn highest_card_takes_the_pile() {
+        let two = Card(Suit::Club, Value::Two, 0);
+        let (model, cmd) = respond(
+            battle(Config::default(), &[1, 2, 3, 4], vec![two]),
+            vec![
+                (3, removed(Value::King, Suit::Heart)),
+                (1, removed(Value::Five, Suit::Heart)),
+                (2, removed(Value::Ace, Suit::Club)),
+                (4, removed(Value::Ten, Suit::Spade)),
+            ],
+        );
+        match model {
+            Model::Battle(_, _, _, table) => assert_eq!(table.pile, vec![]),
+            model => panic!("unexpected model {:?}", model),
+        }
+        let send = |to: u8, cmd: PlayerCmd| SendCmd {
+            to: vec![to],
+            cmd,
+            id: 1,
         };
-        let resp1 = UnableToRemoveCards(2);
-        let resp2 = CardsAdded(3);
-        let pile = vec![];
-        let judge_players =
-            judge_players(pids.clone(), vec![1], resp1, vec![1], resp2, Some(&pile));
         assert_eq!(
-            judge_players,
-            (
-                Model::Error(
-                    pids,
-                    "Model::WarWithResponse: unable to match players: [1], [2] with pids: [1], [1]"
-                        .to_string()
+            cmd,
+            Cmd::SendCmds(vec![
+                send(
+                    2,
+                    Batch(vec![
+                        AddCards(
+                            vec![
+                                two,
+                                Card(Suit::Heart, Value::Five, 0),
+                                Card(Suit::Club, Value::Ace, 0),
+                                Card(Suit::Heart, Value::King, 0),
+                                Card(Suit::Spade, Value::Ten, 0),
+                            ],
+                            Placement::Bottom
+                        ),
+                        RemoveCards(1)
+                    ])
                 ),
-                Cmd::None
+                send(1, RemoveCards(1)),
+                send(3, RemoveCards(1)),
+                send(4, RemoveCards(1)),
+            ])
+        );
+    }
+
+    #[test]
+    fn war_of_the_tied_players() {
+        let (model, cmd) = respond(
+            battle(Config::default(), &[1, 2, 3, 4], vec![]),
+            vec![
+                (1, removed(Value::King, Suit::Heart)),
+                (2, removed(Value::Five, Suit::Heart)),
+                (3, removed(Value::King, Suit::Club)),
+                (4, removed(Value::Ten, Suit::Spade)),
+            ],
+        );
+        match cmd {
+            Cmd::SendCmds(cmds) => {
+                let asked: Vec<(Vec<u8>, _)> =
+                    cmds.into_iter().map(|cmd| (cmd.to, cmd.cmd)).collect();
+                assert_eq!(
+                    asked,
+                    vec![(vec![1], RemoveCards(2)), (vec![3], RemoveCards(2))]
+                );
+            }
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
+        let (model, cmd) = respond(
+            model,
+            vec![
+                (
+                    3,
+                    CardsRemoved(vec![
+                        Card(Suit::Club, Value::Two, 0),
+                        Card(Suit::Club, Value::Three, 0),
+                    ]),
+                ),
+                (
+                    1,
+                    CardsRemoved(vec![
+                        Card(Suit::Heart, Value::Two, 0),
+                        Card(Suit::Heart, Value::Four, 0),
+                    ]),
+                ),
+            ],
+        );
+        match model {
+            Model::Battle(_, _, _, table) => assert_eq!(table.players.len(), 4),
+            model => panic!("unexpected model {:?}", model),
+        }
+        let SendCmd { to, cmd, id: _ } = first(cmd);
+        assert_eq!(to, vec![1]);
+        match cmd {
+            Batch(commands) => match &commands[0] {
+                AddCards(cards, _) => assert_eq!(cards.len(), 8),
+                command => panic!("unexpected command {:?}", command),
+            },
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn face_down_cards_of_a_war() {
+        use crate::cards::FaceDown;
+
+        let war_cards = |face_down| {
+            let config = Config {
+                face_down,
+                ..Config::default()
+            };
+            let (_model, cmd) = respond(
+                battle(config, &[1, 2], vec![]),
+                vec![
+                    (1, removed(Value::Five, Suit::Club)),
+                    (2, removed(Value::Five, Suit::Heart)),
+                ],
+            );
+            first(cmd).cmd
+        };
+        assert_eq!(war_cards(FaceDown::Cards(3)), RemoveCards(4));
+        assert_eq!(war_cards(FaceDown::Cards(0)), RemoveCards(1));
+        assert_eq!(war_cards(FaceDown::ByRank), RemoveCards(6));
+    }
+
+    #[test]
+    fn wrong_nr_of_cards_removed() {
+        let three_cards = |suit| {
+            CardsRemoved(vec![
+                Card(suit, Value::Two, 0),
+                Card(suit, Value::Three, 0),
+                Card(suit, Value::Ace, 0),
+            ])
+        };
+        let (model, _cmd) = respond(
+            battle(Config::default(), &[1, 2], vec![]),
+            vec![(1, three_cards(Suit::Club)), (2, three_cards(Suit::Heart))],
+        );
+        assert_eq!(
+            model,
+            Model::Error(
+                pids_of(4),
+                "Model::Battle: players must have right number of cards".to_string()
             )
         );
     }
 
     #[test]
-    fn wrong_response() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+    fn last_card_war() {
+        let config = Config {
+            last_card_war: true,
+            ..Config::default()
+        };
+        let five = Card(Suit::Club, Value::Five, 0);
+        let (model, cmd) = respond(
+            battle(config, &[1, 2], vec![]),
+            vec![
+                (1, CardsRemoved(vec![five])),
+                (2, removed(Value::Five, Suit::Heart)),
+            ],
+        );
+        assert_eq!(first(cmd).cmd, RemoveUpTo(2));
+        // player 2 has only a king left, it is the face-up card
+        let (model, cmd) = respond(
+            model,
+            vec![
+                (
+                    1,
+                    CardsRemoved(vec![five, Card(Suit::Club, Value::Queen, 0)]),
+                ),
+                (
+                    2,
+                    CardsPartiallyRemoved(vec![Card(Suit::Heart, Value::King, 0)]),
+                ),
+            ],
+        );
+        match model {
+            Model::Battle(_, _, _, table) => assert_eq!(table.cards, 1),
+            model => panic!("unexpected model {:?}", model),
+        }
+        let SendCmd { to, cmd, id: _ } = first(cmd);
+        assert_eq!(to, vec![2]);
+        match cmd {
+            Batch(commands) => match &commands[0] {
+                AddCards(cards, _) => assert_eq!(cards.len(), 5),
+                command => panic!("unexpected command {:?}", command),
+            },
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn rules_decide_battle() {
+        let config = Config {
+            rules: vec![Rule::Beats(Value::Two, Value::Ace)],
+            ..Config::default()
         };
-        let resp1 = UnableToRemoveCards(2);
-        let resp2 = CardsAdded(3);
-        let judge_players = judge_players(pids.clone(), vec![1], resp1, vec![2], resp2, None);
+        let (_model, cmd) = respond(
+            battle(config, &[1, 2], vec![]),
+            vec![
+                (2, removed(Value::Ace, Suit::Heart)),
+                (1, removed(Value::Two, Suit::Club)),
+            ],
+        );
         assert_eq!(
-            judge_players,
-            (Model::Error(pids, "Model::BattleWithRespose received wrong responses: UnableToRemoveCards(2), CardsAdded(3)".to_string()), Cmd::None)
+            first(cmd),
+            SendCmd {
+                to: vec![1],
+                cmd: Batch(vec![
+                    AddCards(
+                        vec![
+                            Card(Suit::Club, Value::Two, 0),
+                            Card(Suit::Heart, Value::Ace, 0)
+                        ],
+                        Placement::Bottom
+                    ),
+                    RemoveCards(1)
+                ]),
+                id: 1,
+            }
         );
     }
 
     #[test]
-    fn wrong_nr_of_cards_removed() {
-        use crate::cards::Suit;
-        use crate::cards::Value;
-
-        let resp1 = CardsRemoved(vec![
-            Card(Suit::Club, Value::Two),
-            Card(Suit::Club, Value::Three),
-            Card(Suit::Diamond, Value::Ace),
-        ]);
-        let resp2 = CardsRemoved(vec![
-            Card(Suit::Heart, Value::Two),
-            Card(Suit::Heart, Value::Three),
-            Card(Suit::Spade, Value::Ace),
-        ]);
-        let (model, cmd) = judge_players(
-            Pids {
-                supervisor: vec![0],
-                player1: vec![1],
-                player2: vec![2],
-            },
-            vec![1],
-            resp1,
-            vec![2],
-            resp2,
-            None,
+    fn players_without_cards_leave() {
+        let (model, _cmd) = respond(
+            battle(Config::default(), &[1, 2, 3], vec![]),
+            vec![
+                (1, UnableToRemoveCards(1)),
+                (2, removed(Value::Five, Suit::Heart)),
+                (3, removed(Value::Ten, Suit::Heart)),
+            ],
         );
-        assert_eq!(
-            (model, cmd),
-            (
-                Model::Error(
-                    Pids {
-                        supervisor: vec![0],
-                        player1: vec![1],
-                        player2: vec![2],
-                    },
-                    "Model::BattleWithRespose: players must have right number of cards".to_string()
-                ),
-                Cmd::None
-            )
+        match model.clone() {
+            Model::Battle(_, _, _, table) => {
+                assert_eq!(table.players, vec![vec![2], vec![3]]);
+            }
+            model => panic!("unexpected model {:?}", model),
+        }
+        let (model, _cmd) = respond(
+            model,
+            vec![
+                (2, UnableToRemoveCards(1)),
+                (3, removed(Value::Two, Suit::Spade)),
+            ],
+        );
+        assert_eq!(model, Model::PlayerWon(pids_of(4), vec![3]));
+        let (model, _cmd) = respond(
+            battle(Config::default(), &[1, 2], vec![]),
+            vec![(2, UnableToRemoveCards(1)), (1, UnableToRemoveCards(1))],
         );
+        assert_eq!(model, Model::Tie(pids_of(4)));
     }
 
     #[test]
-    fn battle_won_by_player_without_pile() {
-        use crate::cards::Suit;
-        use crate::cards::Suit::Club;
-        use crate::cards::Suit::Heart;
-        use crate::cards::Value;
-        use crate::cards::Value::Three;
-        use crate::cards::Value::Two;
-        use crate::game::Cmd::SendCmds;
-        use crate::player::Command::AddCards;
-
-        let resp1 = CardsRemoved(vec![Card(Suit::Club, Value::Two)]);
-        let resp2 = CardsRemoved(vec![Card(Suit::Heart, Value::Three)]);
-        let (model, cmd) = judge_players(
-            Pids {
-                supervisor: vec![0],
-                player1: vec![1],
-                player2: vec![2],
-            },
-            vec![1],
-            resp1,
-            vec![2],
-            resp2,
-            None,
+    fn players_who_resign_leave() {
+        let (model, cmd) = respond(
+            battle(Config::default(), &[1, 2, 3], vec![]),
+            vec![
+                (2, removed(Value::Ace, Suit::Heart)),
+                (1, PlayerResp::Resigned),
+                (3, removed(Value::Ten, Suit::Heart)),
+            ],
         );
-        assert_eq!(
-            (model, cmd),
-            (
-                Model::BattleWonByPlayer(
-                    Pids {
-                        supervisor: vec![0],
-                        player1: vec![1],
-                        player2: vec![2]
-                    },
-                    vec![2]
-                ),
-                SendCmds(
-                    [SendCmd {
-                        to: vec![2],
-                        cmd: AddCards([Card(Club, Two), Card(Heart, Three)].to_vec())
-                    }]
-                    .to_vec()
-                )
-            )
+        match model {
+            Model::Battle(_, _, _, table) => {
+                assert_eq!(table.players, vec![vec![2], vec![3]]);
+            }
+            model => panic!("unexpected model {:?}", model),
+        }
+        match cmd {
+            Cmd::SendCmds(cmds) => {
+                let asked: Vec<Vec<u8>> = cmds.into_iter().map(|cmd| cmd.to).collect();
+                assert_eq!(asked, vec![vec![2], vec![3]]);
+            }
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
+        let (model, _cmd) = respond(
+            battle(Config::default(), &[1, 2, 3], vec![]),
+            vec![(3, PlayerResp::Resigned), (1, PlayerResp::Resigned)],
         );
+        assert_eq!(model, Model::PlayerWon(pids_of(4), vec![2]));
     }
 
     #[test]
-    fn tie_without_pile() {
-        use crate::cards::Suit;
-        use crate::cards::Suit::Club;
-        use crate::cards::Suit::Heart;
-        use crate::cards::Value;
-        use crate::cards::Value::Two;
-        use crate::game::Cmd::SendCmds;
-        use crate::player::Command::RemoveCards;
-
-        let resp1 = CardsRemoved(vec![Card(Suit::Club, Value::Two)]);
-        let resp2 = CardsRemoved(vec![Card(Suit::Heart, Value::Two)]);
-
-        let (model, cmd) = judge_players(
-            Pids {
-                supervisor: vec![0],
-                player1: vec![1],
-                player2: vec![2],
-            },
-            vec![1],
-            resp1,
-            vec![2],
-            resp2,
-            None,
+    fn cards_of_a_player_who_resigns_go_to_the_winner() {
+        use crate::audit::check_update;
+
+        let two = Card(Suit::Club, Value::Two, 0);
+        let (model, _cmd) = respond(
+            battle(Config::default(), &[1, 2, 3], vec![]),
+            vec![(1, CardsRemoved(vec![two]))],
         );
-        assert_eq!(
-            (model, cmd),
-            (
-                Model::War(
-                    Pids {
-                        supervisor: vec![0],
-                        player1: vec![1],
-                        player2: vec![2]
-                    },
-                    vec![Card(Club, Two), Card(Heart, Two)]
-                ),
-                SendCmds(vec![
-                    SendCmd {
-                        to: vec![1],
-                        cmd: RemoveCards(2)
-                    },
-                    SendCmd {
-                        to: vec![2],
-                        cmd: RemoveCards(2)
-                    }
-                ]),
-            )
+        let msg = Msg::ResponseFromPlayer(vec![1], PlayerResp::Resigned, 0);
+        let (resigned, cmd) = update(model.clone(), msg.clone());
+        assert_eq!(check_update(&model, &msg, &resigned, &cmd), Ok(1));
+        match &resigned {
+            Model::Battle(_, _, _, table) => assert_eq!(table.pile, vec![two]),
+            model => panic!("unexpected model {:?}", model),
+        }
+        let (_model, cmd) = respond(
+            resigned,
+            vec![
+                (2, removed(Value::Ace, Suit::Heart)),
+                (3, removed(Value::Ten, Suit::Heart)),
+            ],
         );
+        match first(cmd).cmd {
+            Batch(commands) => assert_eq!(
+                commands[0],
+                AddCards(
+                    vec![
+                        two,
+                        Card(Suit::Heart, Value::Ace, 0),
+                        Card(Suit::Heart, Value::Ten, 0),
+                    ],
+                    Placement::Bottom
+                )
+            ),
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
     }
 
     #[test]
-    fn tie_with_pile() {
-        use crate::cards::Suit;
-        use crate::cards::Suit::*;
-        use crate::cards::Value;
-        use crate::cards::Value::*;
-        use crate::game::Cmd::SendCmds;
-        use crate::player::Command::RemoveCards;
-
-        let resp1 = CardsRemoved(vec![
-            Card(Suit::Club, Value::Two),
-            Card(Suit::Heart, Value::Ace),
-        ]);
-        let resp2 = CardsRemoved(vec![
-            Card(Suit::Heart, Value::Ten),
-            Card(Suit::Spade, Value::Ace),
-        ]);
-        let pile = vec![Card(Suit::Club, Value::Ten), Card(Suit::Heart, Value::Ace)];
-
-        let (model, cmd) = judge_players(
-            Pids {
-                supervisor: vec![0],
-                player1: vec![1],
-                player2: vec![2],
-            },
-            vec![1],
-            resp1,
-            vec![2],
-            resp2,
-            Some(&pile),
+    fn only_fighters_answer() {
+        let (model, _cmd) = respond(
+            battle(Config::default(), &[1, 2, 3], vec![]),
+            vec![(4, CardsAdded(1))],
         );
-
         assert_eq!(
-            (model, cmd),
-            (
-                Model::War(
-                    Pids {
-                        supervisor: vec![0],
-                        player1: vec![1],
-                        player2: vec![2]
-                    },
-                    vec![
-                        Card(Club, Ten),
-                        Card(Heart, Ace),
-                        Card(Club, Two),
-                        Card(Heart, Ace),
-                        Card(Heart, Ten),
-                        Card(Spade, Ace)
-                    ]
-                ),
-                SendCmds(vec![
-                    SendCmd {
-                        to: vec![1],
-                        cmd: RemoveCards(2)
-                    },
-                    SendCmd {
-                        to: vec![2],
-                        cmd: RemoveCards(2)
-                    }
-                ])
-            ),
+            model,
+            Model::Error(
+                pids_of(4),
+                "unexpected response from [4]: CardsAdded(1)".to_string()
+            )
         );
     }
+}
+
+#[cfg(test)]
+mod resign {
+    use super::*;
+    use crate::cards::{Suit, Value};
 
     #[test]
-    fn player_won() {
-        use crate::cards::Suit;
-        use crate::cards::Value;
-
-        let resp1 = UnableToRemoveCards(2);
-        let resp2 = CardsRemoved(vec![
-            Card(Suit::Heart, Value::Ten),
-            Card(Suit::Spade, Value::Ace),
-        ]);
-        let pile = vec![Card(Suit::Club, Value::Ten), Card(Suit::Heart, Value::Ace)];
-
-        let (model, cmd) = judge_players(
-            Pids {
-                supervisor: vec![0],
-                player1: vec![1],
-                player2: vec![2],
-            },
-            vec![1],
-            resp1,
-            vec![2],
-            resp2,
-            Some(&pile),
-        );
+    fn in_every_phase() {
+        let config = Config::default();
+        let state = State {
+            round: 5,
+            turns: 3,
+            ..State::default()
+        };
+        let played = Table {
+            cards: 1,
+            responses: vec![(
+                vec![1],
+                PlayerResp::CardsRemoved(vec![Card(Suit::Club, Value::Two, 0)]),
+            )],
+            ..seated(&pids())
+        };
+        let models = vec![
+            Model::Players(pids(), config.clone(), state.clone(), seated(&pids())),
+            Model::Battle(pids(), config.clone(), state.clone(), played),
+            Model::Hashing(pids(), config.clone(), state.clone(), seated(&pids())),
+            Model::Adjudication(pids(), config, state, seated(&pids())),
+        ];
+        for model in models {
+            let msg = Msg::ResponseFromPlayer(vec![2], PlayerResp::Resigned, 0);
+            assert_eq!(
+                update(model, msg),
+                (Model::PlayerWon(pids(), vec![1]), Cmd::None)
+            );
+        }
+    }
 
+    #[test]
+    fn only_players_resign() {
+        let model = Model::Battle(pids(), Config::default(), State::default(), seated(&pids()));
+        let msg = Msg::ResponseFromPlayer(vec![3], PlayerResp::Resigned, 0);
         assert_eq!(
-            (model, cmd),
-            (
-                Model::Player2Won(Pids {
-                    supervisor: vec![0],
-                    player1: vec![1],
-                    player2: vec![2],
-                }),
-                Cmd::None
-            ),
+            update(model, msg).0,
+            Model::Error(
+                pids(),
+                "[3] resigned from a game it does not play".to_string()
+            )
+        );
+        let msg = Msg::ResponseFromPlayer(vec![1], PlayerResp::Resigned, 0);
+        let (model, _cmd) = update(Model::Tie(pids()), msg);
+        assert_eq!(
+            model,
+            Model::Error(
+                pids(),
+                "Model::Tie received wrong msg: ResponseFromPlayer([1], Resigned, 0)".to_string()
+            )
         );
     }
-
 }
 
+// In debug builds every update is audited, see audit::check_update. Commands
+// for the same player are sent as one batch.
 pub fn update(model: Model, msg: Msg) -> (Model, Cmd) {
-    use crate::cards::shuffled_deck;
-
-    use crate::player::Command::*;
-    use crate::player::Response::CardsAdded;
+    use crate::audit::check_update;
 
-    match (model, msg) {
-        (Model::None(pids), Msg::StartGame) => {
-            let Pids {
-                supervisor: _,
-                player1,
-                player2,
-            } = pids.clone();
-            let deck = shuffled_deck();
-            let (cards1, cards2) = deck.split_at(26);
-            let send_decks = vec![
-                SendCmd {
-                    to: player1,
-                    cmd: AddCards(cards1.to_vec()),
-                },
-                SendCmd {
-                    to: player2,
-                    cmd: AddCards(cards2.to_vec()),
-                },
-            ];
-            (Model::Players(pids), Cmd::SendCmds(send_decks))
+    if cfg!(debug_assertions) {
+        let (new_model, cmd) = step(model.clone(), msg.clone());
+        let cmd = cmd.coalesced();
+        match check_update(&model, &msg, &new_model, &cmd) {
+            Ok(_) => (new_model, cmd),
+            Err(error) => {
+                let error = format!("audit: {}", error);
+                (Model::Error(new_model.pids().clone(), error), Cmd::None)
+            }
         }
-        (Model::None(pids), msg) => {
-            let error = format!("Model::None got msg: {:?}", msg);
-            (Model::Error(pids, error), Cmd::None)
+    } else {
+        let (new_model, cmd) = step(model, msg);
+        (new_model, cmd.coalesced())
+    }
+}
+
+// The deal goes into the hands whatever the placement of won cards. Every
+// player gets its own seed, drawn from the seed of the game.
+fn deal(
+    config: &Config,
+    state: &State,
+    players: &[Vec<u8>],
+    hands: Vec<Vec<Card>>,
+) -> Vec<SendCmd> {
+    use crate::cards::player_seeds;
+    use crate::player::Command::Deal;
+
+    let seeds = player_seeds(config.seed.unwrap_or_default(), players.len());
+    players
+        .iter()
+        .zip(hands)
+        .zip(seeds)
+        .map(|((player, cards), seed)| SendCmd {
+            to: player.clone(),
+            cmd: Deal(cards, seed),
+            id: state.round,
+        })
+        .collect()
+}
+
+// Shoe of the game, the seed and the drawn trump are recorded in the config
+fn shoe(config: Config) -> (crate::cards::Shoe, Config) {
+    use crate::cards::random_seed;
+    use crate::cards::Shoe;
+
+    let seed = match config.seed {
+        Some(seed) => seed,
+        None => random_seed(),
+    };
+    let shoe = Shoe::shuffled(config.deck, config.decks, config.shuffling, seed);
+    let trump = config.trump.draw(&shoe.cards);
+    let config = Config {
+        seed: Some(seed),
+        trump,
+        ..config
+    };
+    (shoe, config)
+}
+
+fn step(model: Model, msg: Msg) -> (Model, Cmd) {
+    if model.is_stale(&msg) {
+        // answer to commands of another round, e.g. delayed or sent twice
+        return (model, Cmd::None);
+    }
+    let msg = match msg {
+        Msg::ResponseFromPlayer(pid, response, id) => {
+            Msg::ResponseFromPlayer(pid, response.unbatched().without_reshuffle(), id)
         }
-        (Model::Players(pids), Msg::ResponseFromPlayer(pid, CardsAdded(26))) => (
-            Model::PlayersWithResponse(pids, pid, CardsAdded(26)),
-            Cmd::None,
-        ),
-        (Model::Players(pids), msg) => {
-            let error = format!("Model::Players got msg: {:?}", msg);
+        msg => msg,
+    };
+    match (model, msg) {
+        (Model::None(pids, config), Msg::StartGame) if config.decks == 0 => {
+            let error = "Model::None: shoe must have at least one deck".to_string();
             (Model::Error(pids, error), Cmd::None)
         }
-        (
-            Model::PlayersWithResponse(pids, pid1, CardsAdded(26)),
-            Msg::ResponseFromPlayer(pid2, CardsAdded(26)),
-        ) => {
-            let Pids {
-                supervisor: _,
-                player1,
-                player2,
-            } = pids.clone();
-            // received correct pids
-            let game_initialized =
-                (&player1, &player2) == (&pid1, &pid2) || (&player1, &player2) == (&pid2, &pid1);
-            match game_initialized {
-                false => (
-                    Model::Error(
-                        pids,
-                        "Model::PlayersWithResponse: received wrong pids".to_string(),
-                    ),
-                    Cmd::None,
-                ),
-                true => {
-                    let remove_cards = vec![
-                        SendCmd {
-                            to: player1,
-                            cmd: RemoveCards(1),
-                        },
-                        SendCmd {
-                            to: player2,
-                            cmd: RemoveCards(1),
-                        },
-                    ];
-                    (Model::Battle(pids), Cmd::SendCmds(remove_cards))
-                }
-            }
+        (model, Msg::ResponseFromPlayer(pid, PlayerResp::Error(error), _)) => {
+            let error = format!("player {:?} refused the command: {}", pid, error);
+            (Model::Error(model.pids().clone(), error), Cmd::None)
         }
-        (Model::PlayersWithResponse(pids, _, _), msg) => {
-            let error = format!("Model::PlayersWithResponse got msg: {:?}", msg);
+        (Model::None(pids, config), Msg::StartGame) => deal_players(pids, config),
+        (Model::None(pids, _), msg) => {
+            let error = format!("Model::None got msg: {:?}", msg);
             (Model::Error(pids, error), Cmd::None)
         }
-        // Game is initialized. Start the battle.
-        (Model::Battle(pids), Msg::ResponseFromPlayer(player, response)) => {
-            (Model::BattleWithResponse(pids, player, response), Cmd::None)
-        }
-        (Model::Battle(pids), msg) => {
-            let error = format!("Model::Battle got msg: {:?}", msg);
-            (Model::Error(pids, error), Cmd::None)
+        (Model::Players(pids, config, state, table), Msg::ResponseFromPlayer(pid, response, _)) => {
+            // the cards of a player who left are not counted
+            let model = (pids, config, state, table);
+            collect(model, (pid, response), Model::Players, judge_deal)
         }
-        (
-            Model::BattleWithResponse(pids, pid1, response1),
-            Msg::ResponseFromPlayer(pid2, response2),
-        ) => judge_players(pids, pid1, response1, pid2, response2, None),
-        (Model::BattleWithResponse(pids, _, _), msg) => {
-            let error = format!("Model::BattleWithResponse received wrong msg: {:?}", msg);
+        (Model::Players(pids, _, _, _), msg) => {
+            let error = format!("Model::Players received wrong msg: {:?}", msg);
             (Model::Error(pids, error), Cmd::None)
         }
-        (Model::BattleWonByPlayer(pids, player), Msg::ResponseFromPlayer(pid, CardsAdded(2))) => {
-            if player == pid {
-                let Pids {
-                    supervisor: _,
-                    player1,
-                    player2,
-                } = pids.clone();
-                let remove_cards = vec![
-                    SendCmd {
-                        to: player1,
-                        cmd: RemoveCards(1),
-                    },
-                    SendCmd {
-                        to: player2,
-                        cmd: RemoveCards(1),
-                    },
-                ];
-                (Model::Battle(pids), Cmd::SendCmds(remove_cards))
-            } else {
-                (
-                    Model::Error(
-                        pids,
-                        "Model::BattleWonByPlayer received wrong pid".to_string(),
-                    ),
-                    Cmd::None,
-                )
-            }
+        (Model::Battle(pids, config, state, table), Msg::ResponseFromPlayer(pid, response, _)) => {
+            let model = (pids, config, state, table);
+            collect(model, (pid, response), Model::Battle, judge_battle)
         }
-        (Model::BattleWonByPlayer(pids, _), msg) => {
-            let error = format!("Model::BattleWonByPlayer received wrong msg: {:?}", msg);
+        (Model::Battle(pids, _, _, _), msg) => {
+            let error = format!("Model::Battle received wrong msg: {:?}", msg);
             (Model::Error(pids, error), Cmd::None)
         }
-        (Model::War(pids, pile), Msg::ResponseFromPlayer(player, response)) => (
-            Model::WarWithResponse(pids, pile, player, response),
-            Cmd::None,
-        ),
-        (Model::War(pids, _), msg) => {
-            let error = format!("Model::War received wrong msg: {:?}", msg);
+        (Model::Hashing(pids, config, state, table), Msg::ResponseFromPlayer(pid, response, _)) => {
+            let model = (pids, config, state, table);
+            collect(model, (pid, response), Model::Hashing, judge_hashes)
+        }
+        (Model::Hashing(pids, _, _, _), msg) => {
+            let error = format!("Model::Hashing received wrong msg: {:?}", msg);
             (Model::Error(pids, error), Cmd::None)
         }
         (
-            Model::WarWithResponse(pids, pile, pid1, response1),
-            Msg::ResponseFromPlayer(pid2, response2),
-        ) => judge_players(pids, pid1, response1, pid2, response2, Some(&pile)),
-        (Model::WarWithResponse(pids, _, _, _), msg) => {
-            let error = format!("Model::WarWithResponse received wrong msg: {:?}", msg);
-            (Model::Error(pids, error), Cmd::None)
+            Model::Adjudication(pids, config, state, table),
+            Msg::ResponseFromPlayer(pid, response, _),
+        ) => {
+            let model = (pids, config, state, table);
+            collect(
+                model,
+                (pid, response),
+                Model::Adjudication,
+                judge_adjudication,
+            )
         }
-        (Model::WarWonByPlayer(pids, player), Msg::ResponseFromPlayer(pid, CardsAdded(_))) => {
-            if player == pid {
-                let Pids {
-                    supervisor: _,
-                    player1,
-                    player2,
-                } = pids.clone();
-                let remove_cards = vec![
-                    SendCmd {
-                        to: player1,
-                        cmd: RemoveCards(1),
-                    },
-                    SendCmd {
-                        to: player2,
-                        cmd: RemoveCards(1),
-                    },
-                ];
-                (Model::Battle(pids), Cmd::SendCmds(remove_cards))
-            } else {
-                (
-                    Model::Error(pids, "Model::WarWonByPlayer received wrong pid".to_string()),
-                    Cmd::None,
-                )
-            }
+        (Model::Adjudication(pids, _, _, _), msg) => {
+            let error = format!("Model::Adjudication received wrong msg: {:?}", msg);
+            (Model::Error(pids, error), Cmd::None)
         }
-        (Model::WarWonByPlayer(pids, _), msg) => {
-            let error = format!("Model::WarWonByPlayer received wrong msg: {:?}", msg);
+        (Model::Tie(pids), msg) => {
+            let error = format!("Model::Tie received wrong msg: {:?}", msg);
             (Model::Error(pids, error), Cmd::None)
         }
-        (Model::Player1Won(pids), msg) => {
-            let error = format!("Model::Player1Won received wrong msg: {:?}", msg);
+        (Model::Adjudicated(pids, _, _), msg) => {
+            let error = format!("Model::Adjudicated received wrong msg: {:?}", msg);
             (Model::Error(pids, error), Cmd::None)
         }
-        (Model::Player2Won(pids), msg) => {
-            let error = format!("Model::Player2Won received wrong msg: {:?}", msg);
+        (Model::Cycle(pids, _, _), msg) => {
+            let error = format!("Model::Cycle received wrong msg: {:?}", msg);
             (Model::Error(pids, error), Cmd::None)
         }
-        (Model::Tie(pids), msg) => {
-            let error = format!("Model::Tie received wrong msg: {:?}", msg);
+        (Model::PlayerWon(pids, _), msg) => {
+            let error = format!("Model::PlayerWon received wrong msg: {:?}", msg);
             (Model::Error(pids, error), Cmd::None)
         }
         (Model::Error(pids, error), _) => (Model::Error(pids, error), Cmd::None),
@@ -908,64 +1750,51 @@ pub fn update(model: Model, msg: Msg) -> (Model, Cmd) {
 #[cfg(test)]
 mod game_update {
     use super::*;
-    use crate::player::Command::AddCards;
+    use crate::cards::Suit;
+    use crate::cards::Value;
+    use crate::player::Command::Deal;
     use crate::player::Response::CardsAdded;
 
     #[test]
     fn update_none_with_wrong_msg() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
-        };
-        let model = Model::None(pids);
-        let msg = Msg::ResponseFromPlayer(vec![1], CardsAdded(23));
+        let model = Model::None(pids(), Config::default());
+        let msg = Msg::ResponseFromPlayer(vec![1], CardsAdded(23), 0);
         let (updated_model, _cmd) = update(model, msg);
         assert_eq!(
             updated_model,
             Model::Error(
-                Pids {
-                    supervisor: vec![0],
-                    player1: vec![1],
-                    player2: vec![2],
-                },
-                "Model::None got msg: ResponseFromPlayer([1], CardsAdded(23))".to_string()
+                pids(),
+                "Model::None got msg: ResponseFromPlayer([1], CardsAdded(23), 0)".to_string()
             )
         );
     }
 
     #[test]
     fn update_players_with_cards_added_26() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+        let config = Config {
+            seed: Some(0),
+            ..Config::default()
         };
-        let model = Model::Players(pids);
-        let msg = Msg::ResponseFromPlayer(vec![1], CardsAdded(26));
+        let model = Model::Players(pids(), config.clone(), State::default(), seated(&pids()));
+        let msg = Msg::ResponseFromPlayer(vec![1], CardsAdded(26), 0);
         let (updated_model, _cmd) = update(model, msg);
+        let table = Table {
+            responses: vec![(vec![1], CardsAdded(26))],
+            ..seated(&pids())
+        };
         assert_eq!(
             updated_model,
-            Model::PlayersWithResponse(
-                Pids {
-                    supervisor: vec![0],
-                    player1: vec![1],
-                    player2: vec![2]
-                },
-                vec![1],
-                CardsAdded(26)
-            ),
+            Model::Players(pids(), config, State::default(), table),
         );
     }
 
     #[test]
     fn update_none_with_msg_pids() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+        let config = Config {
+            seed: Some(42),
+            ..Config::default()
         };
-        let model = Model::None(pids);
+        let model = Model::None(pids(), config.clone());
         let msg = Msg::StartGame;
         let (updated_model, cmd) = update(model, msg);
         let ((to1, cards1), (to2, cards2)) = match cmd {
@@ -977,11 +1806,13 @@ mod game_update {
                     (
                         Some(SendCmd {
                             to: one,
-                            cmd: AddCards(c1),
+                            cmd: Deal(c1, _),
+                            id: 0,
                         }),
                         Some(SendCmd {
                             to: two,
-                            cmd: AddCards(c2),
+                            cmd: Deal(c2, _),
+                            id: 0,
                         }),
                     ) => ((one, c1), (two, c2)),
                     (_, _) => ((vec![0], vec![]), (vec![0], vec![])),
@@ -1000,50 +1831,337 @@ mod game_update {
             println!("{:?}", card);
         }
         println!("");
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+        let state = State::default().next_round();
+        assert_eq!(
+            updated_model,
+            Model::Players(pids(), config, state, seated(&pids()))
+        );
+    }
+
+    #[test]
+    fn update_none_records_random_seed() {
+        let model = Model::None(pids(), Config::default());
+        let (updated_model, _cmd) = update(model, Msg::StartGame);
+        match updated_model {
+            Model::Players(_, Config { seed: Some(_), .. }, _, _) => (),
+            model => panic!("seed not recorded in {:?}", model),
+        }
+    }
+
+    #[test]
+    fn update_none_with_seed_is_reproducible() {
+        let (players, first_cmd) = update(Model::None(pids(), Config::default()), Msg::StartGame);
+        let config = match players {
+            Model::Players(_, config, _, _) => config,
+            model => panic!("unexpected model {:?}", model),
         };
-        assert_eq!(updated_model, Model::Players(pids));
+        let (_model, replayed_cmd) = update(Model::None(pids(), config), Msg::StartGame);
+        assert_eq!(first_cmd, replayed_cmd);
     }
 
     #[test]
     fn update_players_with_response_with_cards_added_26() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+        let table = Table {
+            responses: vec![(vec![1], CardsAdded(26))],
+            ..seated(&pids())
         };
-        let model = Model::PlayersWithResponse(pids, vec![1], CardsAdded(26));
-        let msg = Msg::ResponseFromPlayer(vec![2], CardsAdded(26));
+        let model = Model::Players(pids(), Config::default(), State::default(), table);
+        let msg = Msg::ResponseFromPlayer(vec![2], CardsAdded(26), 0);
         let (updated_model, _cmd) = update(model, msg);
         assert_eq!(
             updated_model,
-            Model::Battle(Pids {
-                supervisor: vec![0],
-                player1: vec![1],
-                player2: vec![2]
-            }),
+            Model::Battle(
+                pids(),
+                Config::default(),
+                State {
+                    turns: 1,
+                    ..State::default()
+                }
+                .next_round(),
+                Table {
+                    cards: 1,
+                    ..seated(&pids())
+                }
+            ),
         );
     }
 
     #[test]
     fn update_players_with_response_with_wrong_pid() {
-        let pids = Pids {
-            supervisor: vec![0],
-            player1: vec![1],
-            player2: vec![2],
+        let table = Table {
+            responses: vec![(vec![1], CardsAdded(26))],
+            ..seated(&pids())
         };
-        let model = Model::PlayersWithResponse(pids.clone(), vec![1], CardsAdded(26));
-        let msg = Msg::ResponseFromPlayer(vec![1], CardsAdded(26));
+        let model = Model::Players(pids(), Config::default(), State::default(), table);
+        let msg = Msg::ResponseFromPlayer(vec![1], CardsAdded(26), 0);
         let (updated_model, _cmd) = update(model, msg);
         assert_eq!(
             updated_model,
             Model::Error(
-                pids,
-                "Model::PlayersWithResponse: received wrong pids".to_string()
+                pids(),
+                "unexpected response from [1]: CardsAdded(26)".to_string()
             ),
         );
     }
+
+    #[test]
+    fn update_none_deals_piquet_deck() {
+        let config = Config {
+            deck: DeckSpec::Piquet,
+            ..Config::default()
+        };
+        let (_model, cmd) = update(Model::None(pids(), config), Msg::StartGame);
+        match cmd {
+            Cmd::SendCmds(cmds) => {
+                for cmd in cmds {
+                    match cmd.cmd {
+                        Deal(cards, _) => assert_eq!(cards.len(), 16),
+                        cmd => panic!("unexpected cmd {:?}", cmd),
+                    }
+                }
+            }
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn update_none_deals_into_the_hands() {
+        let config = Config {
+            seed: Some(42),
+            placement: Placement::Winnings,
+            ..Config::default()
+        };
+        let (_model, cmd) = update(Model::None(pids(), config), Msg::StartGame);
+        let players: Vec<crate::player::Model> = match cmd {
+            Cmd::SendCmds(cmds) => cmds
+                .into_iter()
+                .map(|SendCmd { to, cmd, id }| {
+                    let msg = crate::player::Msg {
+                        from: to,
+                        command: cmd,
+                        id,
+                    };
+                    crate::player::update(crate::player::Model::default(), msg).0
+                })
+                .collect(),
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        };
+        assert!(players.iter().all(|player| player.hand.len() == 26));
+        assert!(players.iter().all(|player| player.winnings.is_empty()));
+        assert_ne!(players[0].seed, players[1].seed);
+    }
+
+    #[test]
+    fn update_none_deal_is_not_picked_up() {
+        use crate::player::Pickup;
+
+        let config = Config {
+            seed: Some(42),
+            ..Config::default()
+        };
+        let (_model, cmd) = update(Model::None(pids(), config), Msg::StartGame);
+        let (dealt, msg) = match cmd {
+            Cmd::SendCmds(cmds) => match cmds[0].cmd.clone() {
+                Deal(cards, seed) => {
+                    let msg = crate::player::Msg {
+                        from: vec![0],
+                        command: Deal(cards.clone(), seed),
+                        id: cmds[0].id,
+                    };
+                    (cards, msg)
+                }
+                cmd => panic!("unexpected cmd {:?}", cmd),
+            },
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        };
+        let high_first = crate::player::Model {
+            pickup: Pickup::HighFirst,
+            ..crate::player::Model::default()
+        };
+        let (player, _cmd) = crate::player::update(high_first, msg);
+        let hand: Vec<Card> = player.hand.into_iter().collect();
+        assert_eq!(hand, dealt);
+        assert!(hand
+            .windows(2)
+            .any(|cards| cards[0].value() > cards[1].value()));
+    }
+
+    #[test]
+    fn update_players_with_piquet_hand_size() {
+        let config = Config {
+            deck: DeckSpec::Piquet,
+            ..Config::default()
+        };
+        let answered = |nr| Table {
+            responses: vec![(vec![1], CardsAdded(nr))],
+            ..seated(&pids())
+        };
+        let model = Model::Players(pids(), config.clone(), State::default(), answered(16));
+        let (updated_model, _cmd) =
+            update(model, Msg::ResponseFromPlayer(vec![2], CardsAdded(16), 0));
+        match updated_model {
+            Model::Battle(_, _, state, _) => assert_eq!(state.turns, 1),
+            model => panic!("unexpected model {:?}", model),
+        }
+        let model = Model::Players(pids(), config, State::default(), answered(26));
+        let (updated_model, _cmd) =
+            update(model, Msg::ResponseFromPlayer(vec![2], CardsAdded(6), 0));
+        assert_eq!(
+            updated_model,
+            Model::Error(
+                pids(),
+                "Model::Players: players got wrong nr of cards".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn update_none_deals_two_deck_shoe() {
+        let config = Config {
+            decks: 2,
+            ..Config::default()
+        };
+        assert_eq!(config.hand_size(2, 0), 52);
+        let (_model, cmd) = update(Model::None(pids(), config), Msg::StartGame);
+        let mut shoe = vec![];
+        match cmd {
+            Cmd::SendCmds(cmds) => {
+                for cmd in cmds {
+                    match cmd.cmd {
+                        Deal(mut cards, _) => {
+                            assert_eq!(cards.len(), 52);
+                            shoe.append(&mut cards);
+                        }
+                        cmd => panic!("unexpected cmd {:?}", cmd),
+                    }
+                }
+            }
+            cmd => panic!("unexpected cmd {:?}", cmd),
+        }
+        let copies = |card: Card| {
+            shoe.iter()
+                .filter(|c| (c.0, c.1) == (card.0, card.1))
+                .count()
+        };
+        assert_eq!(copies(Card(Suit::Spade, Value::Ace, 0)), 2);
+    }
+
+    #[test]
+    fn update_none_records_drawn_trump() {
+        let config = Config {
+            seed: Some(42),
+            trump: Trump::Drawn,
+            ..Config::default()
+        };
+        let (updated_model, cmd) = update(Model::None(pids(), config), Msg::StartGame);
+        let bottom = match cmd {
+            Cmd::SendCmds(cmds) => match cmds.last() {
+                Some(SendCmd {
+                    to: _,
+                    cmd: Deal(cards, _),
+                    id: _,
+                }) => cards.last().cloned(),
+                _ => None,
+            },
+            _ => None,
+        };
+        match (updated_model, bottom) {
+            (Model::Players(_, config, _, _), Some(card)) => {
+                assert_eq!(config.trump, Trump::Fixed(card.0))
+            }
+            (model, card) => panic!("unexpected model {:?} or card {:?}", model, card),
+        }
+    }
+
+    #[test]
+    fn update_none_with_no_decks() {
+        let config = Config {
+            decks: 0,
+            ..Config::default()
+        };
+        let (updated_model, cmd) = update(Model::None(pids(), config), Msg::StartGame);
+        assert_eq!(
+            (updated_model, cmd),
+            (
+                Model::Error(
+                    pids(),
+                    "Model::None: shoe must have at least one deck".to_string()
+                ),
+                Cmd::None
+            )
+        );
+    }
+
+    #[test]
+    fn update_with_player_error() {
+        use crate::player::PlayerError;
+        let model = Model::Battle(pids(), Config::default(), State::default(), seated(&pids()));
+        let msg =
+            Msg::ResponseFromPlayer(vec![2], PlayerResp::Error(PlayerError::NoCardsToRemove), 0);
+        assert_eq!(
+            update(model, msg),
+            (
+                Model::Error(
+                    pids(),
+                    "player [2] refused the command: no cards to remove".to_string()
+                ),
+                Cmd::None
+            )
+        );
+    }
+
+    #[test]
+    fn update_with_stale_response() {
+        let config = Config::default();
+        let state = State {
+            round: 3,
+            ..State::default()
+        };
+        let table = Table {
+            cards: 1,
+            ..seated(&pids())
+        };
+        let model = Model::Battle(pids(), config.clone(), state.clone(), table.clone());
+        let removed = PlayerResp::CardsRemoved(vec![Card(Suit::Club, Value::Two, 0)]);
+        let stale = Msg::ResponseFromPlayer(vec![1], removed.clone(), 2);
+        assert_eq!(update(model.clone(), stale), (model.clone(), Cmd::None));
+        let msg = Msg::ResponseFromPlayer(vec![1], removed.clone(), 3);
+        let table = Table {
+            responses: vec![(vec![1], removed)],
+            ..table
+        };
+        assert_eq!(
+            update(model, msg),
+            (Model::Battle(pids(), config, state, table), Cmd::None)
+        );
+    }
+
+    #[test]
+    fn update_with_batch_response() {
+        let two = Card(Suit::Club, Value::Two, 0);
+        let table = Table {
+            cards: 1,
+            ..seated(&pids())
+        };
+        let model = Model::Battle(pids(), Config::default(), State::default(), table.clone());
+        let msg = Msg::ResponseFromPlayer(
+            vec![1],
+            PlayerResp::Batch(vec![
+                PlayerResp::CardsAdded(2),
+                PlayerResp::CardsRemoved(vec![two]),
+            ]),
+            0,
+        );
+        let table = Table {
+            responses: vec![(vec![1], PlayerResp::CardsRemoved(vec![two]))],
+            ..table
+        };
+        assert_eq!(
+            update(model, msg),
+            (
+                Model::Battle(pids(), Config::default(), State::default(), table),
+                Cmd::None
+            )
+        );
+    }
 }
@@ -1,9 +1,13 @@
 #[macro_use] extern crate rustler;
-use rustler::{Env, NifResult, Term};
+use rustler::resource::ResourceArc;
+use rustler::types::tuple::make_tuple;
+use rustler::{Encoder, Env, NifResult, Term};
 #[macro_use] extern crate serde;
 #[macro_use] extern crate serde_rustler;
 use serde_rustler::{from_term, to_term};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
+mod audit;
 mod cards;
 mod player;
 mod game;
@@ -13,12 +17,91 @@ rustler_export_nifs! {
     [
         ("player_update", 2, player_update),
         ("game_update", 2, game_update),
+        ("player_update", 3, player_update_with_format),
+        ("game_update", 3, game_update_with_format),
+        ("parse_hand", 1, parse_hand),
+        ("audit", 3, audit),
+        ("player_new", 1, player_new),
+        ("player_model", 1, player_model),
     ],
-    None
+    Some(load)
+}
+
+// Player model kept in Rust between calls, Elixir holds only the handle
+struct PlayerHand(Mutex<player::Model>);
+
+// resource_struct_init! implements the resource type inside of load
+#[allow(non_local_definitions)]
+fn load(env: Env, _info: Term) -> bool {
+    resource_struct_init!(PlayerHand, env);
+    true
+}
+
+// PlayerRust.Model or list of cards (one pile)
+fn player_model_from_term(term: Term) -> NifResult<player::Model> {
+    match from_term(term) {
+        Ok(cards::WireCards(cards)) => Ok(cards.into()),
+        Err(_) => from_term(term)
+            .map(player::Model::indexed)
+            .map_err(|err| err.into()),
+    }
+}
+
+// A model is replaced only after an update returned, so a hand poisoned by a
+// panic still holds the model from before
+fn lock(hand: &PlayerHand) -> MutexGuard<'_, player::Model> {
+    hand.0.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Cards of a PlayerRust.Model, list of cards or handle of a player, the
+// model kept by a handle is not copied
+fn player_cards(term: Term) -> NifResult<Vec<cards::Card>> {
+    match term.decode::<ResourceArc<PlayerHand>>() {
+        Ok(hand) => Ok(lock(&hand).cards()),
+        Err(_) => player_model_from_term(term).map(|model| model.cards()),
+    }
+}
+
+// handle of a new player hand, from a model, list of cards or a copy of the
+// hand of another handle
+fn player_new<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let model = match args[0].decode::<ResourceArc<PlayerHand>>() {
+        Ok(hand) => lock(&hand).clone(),
+        Err(_) => player_model_from_term(args[0])?,
+    };
+    Ok(ResourceArc::new(PlayerHand(Mutex::new(model))).encode(env))
+}
+
+// copy of the model kept by the handle
+fn player_model<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let hand: ResourceArc<PlayerHand> = args[0].decode()?;
+    let model = lock(&hand);
+    to_term(env, &*model).map_err(|err| err.into())
+}
+
+// The handle is returned as the new model, only cards moved by the msg are
+// converted
+fn player_hand_update<'a>(
+    env: Env<'a>,
+    hand: ResourceArc<PlayerHand>,
+    msg: player::Msg,
+) -> NifResult<Term<'a>> {
+    // a copy is updated, the hand keeps its cards if the update panics
+    let cmd = {
+        let mut model = lock(&hand);
+        let (new_model, cmd) = player::update(model.clone(), msg);
+        *model = new_model;
+        cmd
+    };
+    let cmd = to_term(env, cmd)?;
+    Ok(make_tuple(env, &[hand.encode(env), cmd]))
 }
 
 fn player_update<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
-    let model: player::Model = from_term(args[0])?;
+    if let Ok(hand) = args[0].decode::<ResourceArc<PlayerHand>>() {
+        return player_hand_update(env, hand, from_term(args[1])?);
+    }
+    let model = player_model_from_term(args[0])?;
     let msg: player::Msg = from_term(args[1])?;
     to_term(env, player::update(model, msg)).map_err(|err| err.into())
 }
@@ -28,3 +111,33 @@ fn game_update<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
     let msg: game::Msg = from_term(args[1])?;
     to_term(env, game::update(model, msg)).map_err(|err| err.into())
 }
+
+// The third argument chooses wire format of cards in the result:
+// "WireFormat::Terms" or "WireFormat::Binary". Both are accepted as input.
+fn player_update_with_format<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let format: cards::WireFormat = from_term(args[2])?;
+    cards::with_wire_format(format, || player_update(env, &args[..2]))
+}
+
+fn game_update_with_format<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let format: cards::WireFormat = from_term(args[2])?;
+    cards::with_wire_format(format, || game_update(env, &args[..2]))
+}
+
+fn parse_hand<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let hand: String = from_term(args[0])?;
+    to_term(env, cards::parse_hand(&hand)).map_err(|err| err.into())
+}
+
+// {:ok, nr_of_cards} or {:error, reason}, see audit::check_game
+fn audit<'a>(env: Env<'a>, args: &[Term<'a>]) -> NifResult<Term<'a>> {
+    let model: game::Model = from_term(args[0])?;
+    let cmd: game::Cmd = from_term(args[1])?;
+    let players: Vec<Term> = args[2].decode()?;
+    let hands = players
+        .into_iter()
+        .map(player_cards)
+        .collect::<NifResult<Vec<_>>>()?;
+    let result = audit::check_game(&model, &cmd, &hands).map_err(|error| error.to_string());
+    to_term(env, result).map_err(|err| err.into())
+}